@@ -8,7 +8,7 @@ impl Component for A {
 
 	fn build(&self, _state: &mut State) -> Self::Child {}
 
-	fn draw<'a, B: Backend>(&self, _state: &State, view: &mut B::View<'a>, font_state: &mut FontState) {
+	fn draw<'a, B: Backend>(&self, _state: &State, view: &mut B::View<'a>, _font_state: &mut FontState) {
 		view.fill(Color(1.0, 1.0, 0.0, 1.0).into());
 	}
 }
@@ -21,7 +21,7 @@ impl Component for B {
 
 	fn build(&self, _state: &mut State) -> Self::Child {}
 
-	fn draw<'a, B: Backend>(&self, _state: &State, view: &mut B::View<'a>, font_state: &mut FontState) {
+	fn draw<'a, B: Backend>(&self, _state: &State, view: &mut B::View<'a>, _font_state: &mut FontState) {
 		view.fill(Color(0.0, 1.0, 1.0, 1.0).into());
 	}
 
@@ -62,7 +62,7 @@ impl Component for C {
 		Label { text: "On it differed repeated wandered required in. Then girl neat why yet knew rose spot. Moreover property we he kindness greatest be oh striking laughter. In me he at collecting affronting principles apartments. Has visitor law attacks pretend you calling own excited painted. Contented attending smallness it oh ye unwilling. Turned favour man two but lovers. Suffer should if waited common person little oh. Improved civility graceful sex few smallest screened settling. Likely active her warmly has. ❤️".to_owned() }
 	}
 
-	fn draw<'a, B: Backend>(&self, _state: &State, view: &mut B::View<'a>, font_state: &mut FontState) {
+	fn draw<'a, B: Backend>(&self, _state: &State, view: &mut B::View<'a>, _font_state: &mut FontState) {
 		view.fill(Color(1.0, 1.0, 1.0, 1.0).into());
 	}
 
@@ -84,13 +84,12 @@ impl Component for MainView {
 		(Yote, C)
 	}
 
-	fn draw<'a, B: Backend>(&self, _state: &State, view: &mut B::View<'a>, font_state: &mut FontState) {
+	fn draw<'a, B: Backend>(&self, _state: &State, view: &mut B::View<'a>, _font_state: &mut FontState) {
 		view.fill(Color(1.0, 0.0, 1.0, 1.0).into());
 	}
 
 	fn layout(&self, state: &mut State) -> Layout {
 		let focused = state.window_focused;
-		state.redraw_on_change(focused);
 
 		let mut layout = Layout::new();
 
@@ -108,9 +107,17 @@ fn main() {
 	// let mut f = FontState::new();
 	// f.find_font(Attrs::new(), 18.0);
 
-	let app = App::new()
-		.with_window(Page::new(MainView));
-	app.run();
+	let page = Page::new(MainView);
+
+	// UNISON_BACKEND=soft renders on the CPU, for machines without a usable GPU
+	match std::env::var("UNISON_BACKEND").as_deref() {
+		Ok("soft") => App::with_backend(unison_backend_soft::SoftBackend::new())
+			.with_window(page)
+			.run(),
+		_ => App::new()
+			.with_window(page)
+			.run(),
+	}
 
 	// let app = SimpleApp::new(MainView {});
 	// app.run();
@@ -0,0 +1,245 @@
+use crate::*;
+
+
+/// A texture that has been uploaded to a [crate::SoftBackend].
+///
+/// Texels are stored as linear RGBA.
+pub struct SoftTexture {
+	width: u32,
+	height: u32,
+	texels: Vec<[f32; 4]>,
+}
+
+impl SoftTexture {
+	pub fn from_texture(tex: &Texture) -> Self {
+		let texels = match tex.format() {
+			TextureFormat::Rgba32F => tex.as_bytes()
+				.chunks_exact(16)
+				.map(|px| {
+					let channel = |i: usize| f32::from_ne_bytes([px[i*4], px[i*4+1], px[i*4+2], px[i*4+3]]);
+					[channel(0), channel(1), channel(2), channel(3)]
+				})
+				.collect(),
+		};
+
+		Self {
+			width: tex.width(),
+			height: tex.height(),
+			texels,
+		}
+	}
+
+	pub fn width(&self) -> u32 {
+		self.width
+	}
+
+	pub fn height(&self) -> u32 {
+		self.height
+	}
+
+	/// Get the texel at the given position, clamping to the edge of the texture.
+	pub fn texel(&self, x: u32, y: u32) -> [f32; 4] {
+		if self.width == 0 || self.height == 0 {
+			return [1.0, 1.0, 1.0, 1.0];
+		}
+
+		let x = x.min(self.width - 1) as usize;
+		let y = y.min(self.height - 1) as usize;
+
+		self.texels[x + y * self.width as usize]
+	}
+}
+
+
+/// Where a quad takes its texels from.
+#[derive(Clone, Copy)]
+pub enum TexSource<'a> {
+	/// Map the texture onto the quad 1:1, starting at the given texel.
+	Offset(&'a SoftTexture, (u32, u32)),
+	/// Stretch the whole texture over the quad.
+	Stretch(&'a SoftTexture),
+}
+
+
+/// An in-memory RGBA framebuffer.
+///
+/// Pixels are stored as linear RGBA and blended like the wgpu quad pipeline does
+/// (`color * texel`, alpha blending).
+pub struct Framebuffer {
+	width: u32,
+	height: u32,
+	pixels: Vec<[f32; 4]>,
+}
+
+impl Framebuffer {
+	pub fn new(size: (u32, u32)) -> Self {
+		Self {
+			width: size.0,
+			height: size.1,
+			pixels: vec![[0.0, 0.0, 0.0, 0.0]; size.0 as usize * size.1 as usize],
+		}
+	}
+
+	pub fn size(&self) -> (u32, u32) {
+		(self.width, self.height)
+	}
+
+	/// Get a slice of all pixels, row by row.
+	pub fn pixels(&self) -> &[[f32; 4]] {
+		&self.pixels
+	}
+
+	/// Resize the framebuffer, discarding its contents.
+	pub fn resize(&mut self, size: (u32, u32)) {
+		self.width = size.0;
+		self.height = size.1;
+
+		self.pixels.clear();
+		self.pixels.resize(size.0 as usize * size.1 as usize, [0.0, 0.0, 0.0, 0.0]);
+	}
+
+	/// Overwrite every pixel with a color.
+	pub fn clear(&mut self, color: Color) {
+		let color: [f32; 4] = color.into();
+		self.pixels.fill(color);
+	}
+
+	/// Rasterize a quad, clipping it to the framebuffer.
+	pub fn draw_quad(&mut self, pos: (i32, i32), size: (u32, u32), color: Color, tex: Option<TexSource>) {
+		self.draw_quad_clipped(pos, size, color, tex, Rect::new((0, 0), self.size()))
+	}
+
+	/// Rasterize a quad, clipping it to `clip` and the framebuffer.
+	pub fn draw_quad_clipped(&mut self, pos: (i32, i32), size: (u32, u32), color: Color, tex: Option<TexSource>, clip: Rect) {
+		let color: [f32; 4] = color.into();
+
+		let clip = clip.intersect(&Rect::new((0, 0), self.size()));
+
+		let from_x = pos.0.max(clip.x);
+		let from_y = pos.1.max(clip.y);
+		let to_x = (pos.0 as i64 + size.0 as i64).min(clip.x as i64 + clip.width as i64) as i32;
+		let to_y = (pos.1 as i64 + size.1 as i64).min(clip.y as i64 + clip.height as i64) as i32;
+
+		for y in from_y..to_y {
+			for x in from_x..to_x {
+				let local = ((x - pos.0) as u32, (y - pos.1) as u32);
+
+				let texel = match tex {
+					Some(TexSource::Offset(tex, offset)) => tex.texel(offset.0 + local.0, offset.1 + local.1),
+					Some(TexSource::Stretch(tex)) => tex.texel(
+						(local.0 as u64 * tex.width() as u64 / size.0 as u64) as u32,
+						(local.1 as u64 * tex.height() as u64 / size.1 as u64) as u32,
+					),
+					None => [1.0, 1.0, 1.0, 1.0],
+				};
+
+				let src = [
+					(color[0] * texel[0]).clamp(0.0, 1.0),
+					(color[1] * texel[1]).clamp(0.0, 1.0),
+					(color[2] * texel[2]).clamp(0.0, 1.0),
+					(color[3] * texel[3]).clamp(0.0, 1.0),
+				];
+
+				let dst = &mut self.pixels[x as usize + y as usize * self.width as usize];
+				blend(dst, src);
+			}
+		}
+	}
+
+	/// Write the framebuffer into a buffer of `0RGB` pixels, encoding the colors as sRGB.
+	pub fn write_xrgb(&self, out: &mut [u32]) {
+		for (out, px) in out.iter_mut().zip(self.pixels.iter()) {
+			let r = linear_to_srgb(px[0]) as u32;
+			let g = linear_to_srgb(px[1]) as u32;
+			let b = linear_to_srgb(px[2]) as u32;
+
+			*out = (r << 16) | (g << 8) | b;
+		}
+	}
+}
+
+/// Blend `src` over `dst` (standard alpha blending).
+fn blend(dst: &mut [f32; 4], src: [f32; 4]) {
+	let a = src[3];
+
+	dst[0] = src[0] * a + dst[0] * (1.0 - a);
+	dst[1] = src[1] * a + dst[1] * (1.0 - a);
+	dst[2] = src[2] * a + dst[2] * (1.0 - a);
+	dst[3] = a + dst[3] * (1.0 - a);
+}
+
+/// Encode a linear color channel as an 8 bit sRGB value.
+pub fn linear_to_srgb(c: f32) -> u8 {
+	let c = c.clamp(0.0, 1.0);
+
+	let s = if c <= 0.0031308 {
+		c * 12.92
+	} else {
+		1.055 * c.powf(1.0 / 2.4) - 0.055
+	};
+
+	(s * 255.0 + 0.5) as u8
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_draw_quad() {
+		let mut fb = Framebuffer::new((4, 4));
+		fb.clear(Color(0.0, 0.0, 0.0, 1.0));
+
+		// partially outside of the framebuffer, has to be clipped
+		fb.draw_quad((-1, 2), (2, 8), Color(1.0, 0.0, 0.0, 1.0), None);
+
+		let px = |x: usize, y: usize| fb.pixels()[x + y * 4];
+
+		assert_eq!(px(0, 2), [1.0, 0.0, 0.0, 1.0]);
+		assert_eq!(px(0, 3), [1.0, 0.0, 0.0, 1.0]);
+		assert_eq!(px(1, 2), [0.0, 0.0, 0.0, 1.0]);
+		assert_eq!(px(0, 1), [0.0, 0.0, 0.0, 1.0]);
+
+		// half transparent white over black
+		fb.draw_quad((3, 0), (1, 1), Color(1.0, 1.0, 1.0, 0.5), None);
+		assert_eq!(fb.pixels()[3], [0.5, 0.5, 0.5, 1.0]);
+	}
+
+	#[test]
+	fn test_draw_quad_clipped() {
+		let mut fb = Framebuffer::new((4, 4));
+		fb.clear(Color(0.0, 0.0, 0.0, 1.0));
+
+		// the clip rect reaches past the framebuffer as well
+		fb.draw_quad_clipped((0, 0), (4, 4), Color(1.0, 0.0, 0.0, 1.0), None, Rect::new((2, -1), (8, 2)));
+
+		let px = |x: usize, y: usize| fb.pixels()[x + y * 4];
+
+		assert_eq!(px(2, 0), [1.0, 0.0, 0.0, 1.0]);
+		assert_eq!(px(3, 0), [1.0, 0.0, 0.0, 1.0]);
+		assert_eq!(px(1, 0), [0.0, 0.0, 0.0, 1.0]);
+		assert_eq!(px(2, 1), [0.0, 0.0, 0.0, 1.0]);
+	}
+
+	#[test]
+	fn test_draw_textured_quad() {
+		let mut tex = Texture::new(2, 1, TextureFormat::Rgba32F);
+		let data: Vec<u8> = [0.0f32, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0].iter()
+			.flat_map(|f| f.to_ne_bytes())
+			.collect();
+		tex.copy_from_slice(&data);
+
+		let tex = SoftTexture::from_texture(&tex);
+
+		let mut fb = Framebuffer::new((2, 1));
+		fb.clear(Color(0.0, 0.0, 0.0, 1.0));
+
+		// only the second texel is opaque
+		fb.draw_quad((0, 0), (1, 1), Color(0.0, 1.0, 0.0, 1.0), Some(TexSource::Offset(&tex, (1, 0))));
+		fb.draw_quad((1, 0), (1, 1), Color(0.0, 1.0, 0.0, 1.0), Some(TexSource::Offset(&tex, (0, 0))));
+
+		assert_eq!(fb.pixels()[0], [0.0, 1.0, 0.0, 1.0]);
+		assert_eq!(fb.pixels()[1], [0.0, 0.0, 0.0, 1.0]);
+	}
+}
@@ -0,0 +1,9 @@
+pub(crate) use unison_backend::types::*;
+
+mod soft_backend;
+pub use soft_backend::*;
+
+mod framebuffer;
+pub use framebuffer::*;
+
+pub use softbuffer::SoftBufferError;
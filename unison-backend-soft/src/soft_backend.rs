@@ -0,0 +1,325 @@
+use crate::*;
+use unison_backend::*;
+
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+
+
+/// A backend that rasterizes everything on the CPU.
+///
+/// Useful for machines without a usable GPU.
+pub struct SoftBackend {
+	pub image_cache: HashMap<TextureId, SoftTexture>,
+}
+
+impl SoftBackend {
+	pub fn new() -> Self {
+		Self {
+			image_cache: HashMap::new(),
+		}
+	}
+}
+
+impl Default for SoftBackend {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Backend for SoftBackend {
+	type View<'a> = SoftView<'a> where Self: 'a;
+	type Surface = SoftSurface;
+
+	/// # Panics
+	///
+	/// Panics if softbuffer can't attach to `window`. [Backend::create_surface] has no way to
+	/// report the error yet, and there is nothing to render into without a surface.
+	fn create_surface(&self, window: &winit::window::Window) -> Self::Surface {
+		let size = window.inner_size();
+		SoftSurface::new(window, (size.width, size.height)).expect("failed to create a surface for the window")
+	}
+
+	fn create_offscreen_surface(&self, size: (u32, u32)) -> Self::Surface {
+		SoftSurface::new_offscreen(size)
+	}
+
+	fn create_view<'a>(&'a mut self, surface: &'a mut Self::Surface) -> Self::View<'a> {
+		SoftView::new(self, surface)
+	}
+
+	fn upload_texture(&mut self, tex: &Texture) -> TextureId {
+		static TEX_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+		let id = TextureId::new(TEX_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed));
+
+		self.image_cache.insert(id, SoftTexture::from_texture(tex));
+
+		id
+	}
+}
+
+
+/// Blits a [Framebuffer] into a window.
+struct Presenter {
+	// the surface has to be dropped before the context
+	surface: softbuffer::Surface,
+	_context: softbuffer::Context,
+}
+
+impl Presenter {
+	fn new(window: &winit::window::Window) -> Result<Self, SoftBufferError> {
+		// SAFETY: the window outlives the surface, as the surface is dropped before the window
+		let context = unsafe { softbuffer::Context::new(window) }?;
+		let surface = unsafe { softbuffer::Surface::new(&context, window) }?;
+
+		Ok(Self {
+			surface,
+			_context: context,
+		})
+	}
+
+	fn present(&mut self, framebuffer: &Framebuffer) -> Result<(), SoftBufferError> {
+		let (width, height) = framebuffer.size();
+
+		let (width, height) = match (NonZeroU32::new(width), NonZeroU32::new(height)) {
+			(Some(w), Some(h)) => (w, h),
+			_ => return Ok(()),
+		};
+
+		self.surface.resize(width, height)?;
+
+		let mut buf = self.surface.buffer_mut()?;
+		framebuffer.write_xrgb(&mut buf);
+		buf.present()
+	}
+}
+
+pub struct SoftSurface {
+	presenter: Option<Presenter>,
+	framebuffer: Framebuffer,
+	window_size: (u32, u32),
+}
+
+impl SoftSurface {
+	/// Create a surface that presents into `window`.
+	/// 
+	/// Fails if the platform of the window is not supported.
+	pub fn new(window: &winit::window::Window, window_size: (u32, u32)) -> Result<Self, SoftBufferError> {
+		Ok(Self {
+			presenter: Some(Presenter::new(window)?),
+			framebuffer: Framebuffer::new(window_size),
+			window_size,
+		})
+	}
+
+	/// Create a surface that is not bound to a window.
+	pub fn new_offscreen(size: (u32, u32)) -> Self {
+		Self {
+			presenter: None,
+			framebuffer: Framebuffer::new(size),
+			window_size: size,
+		}
+	}
+
+	pub fn reconfigure(&mut self, window_size: (u32, u32)) {
+		self.window_size = window_size;
+		self.framebuffer.resize(window_size);
+	}
+
+	pub fn framebuffer(&self) -> &Framebuffer {
+		&self.framebuffer
+	}
+
+	/// Copy the framebuffer into the window. Does nothing for offscreen surfaces.
+	pub fn present(&mut self) -> Result<(), SoftBufferError> {
+		match &mut self.presenter {
+			Some(presenter) => presenter.present(&self.framebuffer),
+			None => Ok(()),
+		}
+	}
+
+	/// Copy the contents of an offscreen surface into a [Texture].
+	pub fn read_pixels(&self) -> Option<Texture> {
+		if self.presenter.is_some() {
+			return None;
+		}
+
+		let (width, height) = self.framebuffer.size();
+
+		let mut data = Vec::with_capacity(width as usize * height as usize * TextureFormat::Rgba32F.pixel_size());
+
+		for px in self.framebuffer.pixels() {
+			let encoded = [linear_to_srgb(px[0]), linear_to_srgb(px[1]), linear_to_srgb(px[2]), (px[3].clamp(0.0, 1.0) * 255.0 + 0.5) as u8];
+
+			for channel in encoded {
+				data.extend_from_slice(&(channel as f32 / 255.0).to_ne_bytes());
+			}
+		}
+
+		let mut out = Texture::new(width, height, TextureFormat::Rgba32F);
+		out.copy_from_slice(&data);
+
+		Some(out)
+	}
+}
+
+impl Surface<SoftBackend> for SoftSurface {
+	fn reconfigure(&mut self, _bcknd: &SoftBackend, window_size: (u32, u32)) {
+		self.reconfigure(window_size);
+	}
+
+	fn read_pixels(&mut self, _bcknd: &mut SoftBackend) -> Option<Texture> {
+		SoftSurface::read_pixels(self)
+	}
+}
+
+
+pub struct SoftView<'a> {
+	bcknd: &'a mut SoftBackend,
+	surface: &'a mut SoftSurface,
+	window_size: (u32, u32),
+	state: smallvec::SmallVec<[SoftViewState; 8]>,
+}
+
+impl<'a> SoftView<'a> {
+	pub fn new(bcknd: &'a mut SoftBackend, surface: &'a mut SoftSurface) -> Self {
+		surface.framebuffer.clear(Color(0.0, 0.0, 0.0, 1.0));
+
+		let window_size = surface.window_size;
+
+		let mut state = smallvec::SmallVec::new();
+		state.push(SoftViewState::new(window_size));
+
+		Self {
+			bcknd,
+			surface,
+			window_size,
+			state,
+		}
+	}
+
+	pub fn get_state(&self) -> &SoftViewState {
+		self.state.last().unwrap() // state is never empty
+	}
+
+	pub fn get_state_mut(&mut self) -> &mut SoftViewState {
+		self.state.last_mut().unwrap() // state is never empty
+	}
+}
+
+impl<'a> View for SoftView<'a> {
+	type B = SoftBackend;
+
+	fn push(&mut self) {
+		self.state.push(*self.get_state())
+	}
+
+	fn restore(&mut self) {
+		self.state.pop();
+
+		if self.state.is_empty() {
+			self.state.push(SoftViewState::new(self.window_size))
+		}
+	}
+
+	fn reset_viewport(&mut self) {
+		*self.get_state_mut() = SoftViewState::new(self.window_size);
+	}
+
+	fn viewport_size(&self) -> (u32, u32) {
+		self.get_state().size
+	}
+
+	fn viewport_pos(&self) -> (i32, i32) {
+		self.get_state().pos
+	}
+
+	fn set_viewport(&mut self, rect: Rect) {
+		let state = self.get_state_mut();
+		state.pos = rect.pos();
+		state.size = rect.size();
+	}
+
+	fn set_viewport_horizontal(&mut self, offset: u32, width: u32) {
+		let state = self.get_state_mut();
+		state.pos.0 += offset as i32;
+		state.size.0 = width;
+	}
+
+	fn set_viewport_vertical(&mut self, offset: u32, height: u32) {
+		let state = self.get_state_mut();
+		state.pos.1 += offset as i32;
+		state.size.1 = height;
+	}
+
+	fn apply_bounds(&mut self, bounds: Bounds) {
+		let state = self.get_state_mut();
+		let rect = Rect::new(state.pos, state.size).shrink(bounds);
+
+		state.pos = rect.pos();
+		state.size = rect.size();
+	}
+
+	fn set_clip(&mut self, rect: Rect) {
+		let state = self.get_state_mut();
+		state.clip = state.clip.intersect(&rect);
+	}
+
+	fn fill(&mut self, finish: Finish) {
+		let state = *self.get_state();
+		let pos = state.pos;
+
+		match finish {
+			Finish::Color(c) => self.surface.framebuffer.draw_quad_clipped(pos, state.size, c, None, state.clip),
+			Finish::Texture(id) => {
+				let tex = self.bcknd.image_cache.get(&id).map(TexSource::Stretch);
+				self.surface.framebuffer.draw_quad_clipped(pos, state.size, Color(1.0, 1.0, 1.0, 1.0), tex, state.clip)
+			}
+		}
+	}
+
+	fn draw_rect(&mut self, pos: (i32, i32), size: (u32, u32), color: Color, tex: Option<TextureId>, tex_offset: Option<(u32, u32)>) {
+		let state = *self.get_state();
+
+		let pos = (state.pos.0 + pos.0, state.pos.1 + pos.1);
+
+		let tex = tex
+			.and_then(|id| self.bcknd.image_cache.get(&id))
+			.map(|tex| match tex_offset {
+				Some(offset) => TexSource::Offset(tex, offset),
+				None => TexSource::Stretch(tex),
+			});
+
+		self.surface.framebuffer.draw_quad_clipped(pos, size, color, tex, state.clip)
+	}
+
+	fn submit(self) {
+		// a frame that cannot be presented is dropped, the next one tries again
+		if let Err(err) = self.surface.present() {
+			eprintln!("unison-backend-soft: failed to present a frame: {err}");
+		}
+	}
+
+	fn backend(&mut self) -> &mut Self::B {
+		self.bcknd
+	}
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct SoftViewState {
+	pos: (i32, i32),
+	size: (u32, u32),
+	/// Nothing is drawn outside of this rectangle.
+	clip: Rect,
+}
+
+impl SoftViewState {
+	pub fn new(window_size: (u32, u32)) -> Self {
+		Self {
+			pos: (0, 0),
+			size: window_size,
+			clip: Rect::new((0, 0), window_size),
+		}
+	}
+}
@@ -33,7 +33,7 @@ impl<const VC: usize, const IC: usize> QuadPipeline<VC, IC> {
 	fn create_fallback_tex(device: &wgpu::Device, queue: &wgpu::Queue) -> (wgpu::TextureView, wgpu::Sampler) {
 		use wgpu::util::DeviceExt;
 
-		let tex = device.create_texture_with_data(&queue, &wgpu::TextureDescriptor {
+		let tex = device.create_texture_with_data(queue, &wgpu::TextureDescriptor {
 			label: Some("Quad Fallback Texture"),
 			size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
 			mip_level_count: 1,
@@ -70,7 +70,7 @@ impl<const VC: usize, const IC: usize> QuadPipeline<VC, IC> {
 		let mut samplers: [&wgpu::Sampler; TEXTURE_QUEUE_SIZE as usize] = [fallback_sampler; TEXTURE_QUEUE_SIZE as usize];
 
 
-		for (_, id) in texture_queue.iter().enumerate() {
+		for id in texture_queue.iter() {
 			let tex = bcknd.image_cache.get(id).ok_or(())?;
 
 			v.push(tex.create_view(&wgpu::TextureViewDescriptor {
@@ -87,7 +87,7 @@ impl<const VC: usize, const IC: usize> QuadPipeline<VC, IC> {
 
 		let bind_group = bcknd.device.create_bind_group(&wgpu::BindGroupDescriptor {
 			label: Some("Pipeline2d BindGroup"),
-			layout: layout, entries: &[
+			layout, entries: &[
 				wgpu::BindGroupEntry {
 					binding: 0,
 					resource: wgpu::BindingResource::TextureViewArray(&views),
@@ -127,7 +127,7 @@ impl<const VC: usize, const IC: usize> QuadPipeline<VC, IC> {
 				wgpu::BindGroupEntry {
 					binding: 0,
 					resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-						buffer: &camera_buffer,
+						buffer: camera_buffer,
 						offset: 0,
 						size: None,
 					}),
@@ -138,7 +138,7 @@ impl<const VC: usize, const IC: usize> QuadPipeline<VC, IC> {
 		(uniform_bind_group_layout, uniform_bind_group)
 	}
 
-	pub fn new(bcknd: &WgpuBackend, surface_config: &wgpu::SurfaceConfiguration, window_size: (u32, u32)) -> Self {
+	pub fn new(bcknd: &WgpuBackend, format: wgpu::TextureFormat, window_size: (u32, u32)) -> Self {
 		use wgpu::util::DeviceExt;
 
 		let camera = CameraUniform::new((window_size.0 as f32, window_size.1 as f32).into());
@@ -215,7 +215,7 @@ impl<const VC: usize, const IC: usize> QuadPipeline<VC, IC> {
 				entry_point: "fs_main",
 				targets: &[
 					Some(wgpu::ColorTargetState {
-						format: surface_config.format,
+						format,
 						blend: Some(wgpu::BlendState::ALPHA_BLENDING),
 						write_mask: wgpu::ColorWrites::ALL,
 					})
@@ -296,6 +296,7 @@ impl<const VC: usize, const IC: usize> QuadPipeline<VC, IC> {
 		Ok(())
 	}
 
+	#[allow(clippy::result_unit_err)]
 	pub fn queue_texture(&mut self, bcknd: &WgpuBackend, tex: TextureId, end_view: &wgpu::TextureView) -> Result<u32> {
 		if self.texture_queue.len() == TEXTURE_QUEUE_SIZE as usize {
 			self.flush(end_view, bcknd)?;
@@ -306,6 +307,7 @@ impl<const VC: usize, const IC: usize> QuadPipeline<VC, IC> {
 		Ok(index as u32 + 1) // textures within the queue will be indexed starting from 1
 	}
 
+	#[allow(clippy::result_unit_err, clippy::too_many_arguments)]
 	pub fn queue_quad(&mut self, bcknd: &WgpuBackend, pos: (i32, i32), size: (u32, u32), color: Color, tex: Option<TextureId>, tex_coords: Option<TexCoords>, view: &wgpu::TextureView) -> Result<()> {
 		use ultraviolet::*;
 
@@ -325,11 +327,7 @@ impl<const VC: usize, const IC: usize> QuadPipeline<VC, IC> {
 			0
 		};
 
-		let tex_coords = if let Some(t) = tex_coords {
-			t
-		} else {
-			([0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0])
-		};
+		let tex_coords = tex_coords.unwrap_or(([0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]));
 
 		let vertices = &[
 			QuadVertex {
@@ -371,6 +369,7 @@ impl<const VC: usize, const IC: usize> QuadPipeline<VC, IC> {
 		self.indices.clear();
 	}
 
+	#[allow(clippy::result_unit_err)]
 	pub fn flush(&mut self, view: &wgpu::TextureView, bcknd: &WgpuBackend) -> Result<()> {
 		let clear = self.clear_queued.take();
 
@@ -381,7 +380,7 @@ impl<const VC: usize, const IC: usize> QuadPipeline<VC, IC> {
 		bcknd.queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
 		bcknd.queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&self.indices));
 
-		if self.texture_queue.len() > 0 {
+		if !self.texture_queue.is_empty() {
 
 			self.texture_bind_group = Self::create_texture_bind_group(
 				bcknd,
@@ -438,7 +437,7 @@ impl<const VC: usize, const IC: usize> QuadPipeline<VC, IC> {
 }
 
 
-type TexCoords = ([f32; 2], [f32; 2], [f32; 2], [f32; 2]);
+pub(crate) type TexCoords = ([f32; 2], [f32; 2], [f32; 2], [f32; 2]);
 
 
 #[repr(C)]
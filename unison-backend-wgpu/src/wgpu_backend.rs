@@ -59,6 +59,10 @@ impl Backend for WgpuBackend {
 		WgpuSurface::new(self, unsafe { self.instance.create_surface(window) }.unwrap(), (size.width, size.height))
 	}
 
+	fn create_offscreen_surface(&self, size: (u32, u32)) -> Self::Surface {
+		WgpuSurface::new_offscreen(self, size)
+	}
+
 	fn create_view<'a>(&'a mut self, surface: &'a mut Self::Surface) -> Self::View<'a> {
 		WgpuView::new(self, surface)
 	}
@@ -92,9 +96,22 @@ fn texture_format_to_wgpu(format: TextureFormat) -> wgpu::TextureFormat {
 	}
 }
 
+/// Format of the textures offscreen surfaces render into.
+const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// What a [WgpuSurface] renders into.
+enum SurfaceTarget {
+	Window {
+		surface: wgpu::Surface,
+		tex: Option<wgpu::SurfaceTexture>,
+	},
+	Offscreen {
+		tex: wgpu::Texture,
+	},
+}
+
 pub struct WgpuSurface {
-	surface: wgpu::Surface,
-	tex: Option<wgpu::SurfaceTexture>,
+	target: SurfaceTarget,
 	view: Option<wgpu::TextureView>,
 	pipeline: QuadPipeline,
 	window_size: (u32, u32),
@@ -113,47 +130,167 @@ impl WgpuSurface {
 		}
 	}
 
+	fn create_offscreen_texture(bcknd: &WgpuBackend, size: (u32, u32)) -> wgpu::Texture {
+		bcknd.device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("Offscreen Surface Texture"),
+			size: wgpu::Extent3d { width: size.0, height: size.1, depth_or_array_layers: 1 },
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: OFFSCREEN_FORMAT,
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+			view_formats: &[],
+		})
+	}
+
 	pub fn new(bcknd: &WgpuBackend, surface: wgpu::Surface, window_size: (u32, u32)) -> Self {
 		let surface_caps = surface.get_capabilities(&bcknd.adapter);
 		let surface_config = Self::create_surface_config(surface_caps, window_size);
 
-		let pipeline = QuadPipeline::new(bcknd, &surface_config, window_size);
+		let pipeline = QuadPipeline::new(bcknd, surface_config.format, window_size);
 
 		Self {
-			surface,
-			tex: None,
+			target: SurfaceTarget::Window { surface, tex: None },
 			view: None,
 			pipeline,
 			window_size,
 		}
 	}
 
+	/// Create a surface that renders into a texture instead of a window.
+	/// 
+	/// wgpu cannot create empty textures, so the size is at least 1×1.
+	pub fn new_offscreen(bcknd: &WgpuBackend, size: (u32, u32)) -> Self {
+		let size = (size.0.max(1), size.1.max(1));
+		let pipeline = QuadPipeline::new(bcknd, OFFSCREEN_FORMAT, size);
+
+		Self {
+			target: SurfaceTarget::Offscreen { tex: Self::create_offscreen_texture(bcknd, size) },
+			view: None,
+			pipeline,
+			window_size: size,
+		}
+	}
+
 	pub fn reconfigure(&mut self, bcknd: &WgpuBackend, window_size: (u32, u32)) {
+		// just like on creation, the textures cannot be empty
+		let window_size = (window_size.0.max(1), window_size.1.max(1));
 		self.window_size = window_size;
 
-		let surface_caps = self.surface.get_capabilities(&bcknd.adapter);
-		let surface_config = Self::create_surface_config(surface_caps, window_size);
-		self.surface.configure(&bcknd.device, &surface_config);
+		match &mut self.target {
+			SurfaceTarget::Window { surface, .. } => {
+				let surface_caps = surface.get_capabilities(&bcknd.adapter);
+				let surface_config = Self::create_surface_config(surface_caps, window_size);
+				surface.configure(&bcknd.device, &surface_config);
+			},
+			SurfaceTarget::Offscreen { tex } => {
+				*tex = Self::create_offscreen_texture(bcknd, window_size);
+			},
+		}
 
 		self.pipeline.reconfigure(bcknd, window_size);
 	}
 
 	pub fn ensure_surface_texture(&mut self) {
-		if self.tex.is_none() {
-			let tex = self.surface.get_current_texture().unwrap();
-			
-			let view = tex.texture.create_view(&wgpu::TextureViewDescriptor {
-				..Default::default()
-			});
-
-			self.tex = Some(tex);
-			self.view = Some(view);
+		if self.view.is_some() {
+			return;
 		}
+
+		let view = match &mut self.target {
+			SurfaceTarget::Window { surface, tex } => {
+				let t = surface.get_current_texture().unwrap();
+				let view = t.texture.create_view(&wgpu::TextureViewDescriptor::default());
+				*tex = Some(t);
+				view
+			},
+			SurfaceTarget::Offscreen { tex } => tex.create_view(&wgpu::TextureViewDescriptor::default()),
+		};
+
+		self.view = Some(view);
 	}
 
-	pub fn get_current_texture(&mut self) -> &wgpu::SurfaceTexture {
+	pub fn get_current_texture(&mut self) -> &wgpu::Texture {
 		self.ensure_surface_texture();
-		self.tex.as_ref().unwrap() // TODO
+
+		match &self.target {
+			SurfaceTarget::Window { tex, .. } => &tex.as_ref().unwrap().texture, // ensured above
+			SurfaceTarget::Offscreen { tex } => tex,
+		}
+	}
+
+	/// Present the current frame, if this surface belongs to a window.
+	fn present(&mut self) {
+		self.view.take();
+
+		if let SurfaceTarget::Window { tex, .. } = &mut self.target {
+			if let Some(t) = tex.take() {
+				t.present();
+			}
+		}
+	}
+
+	/// Copy the contents of an offscreen surface into a [Texture].
+	pub fn read_pixels(&mut self, bcknd: &WgpuBackend) -> Option<Texture> {
+		let tex = match &self.target {
+			SurfaceTarget::Offscreen { tex } => tex,
+			SurfaceTarget::Window { .. } => return None,
+		};
+
+		let (width, height) = self.window_size;
+
+		// rows of a texture copy have to be aligned
+		let unpadded_row = width * 4;
+		let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+		let padded_row = unpadded_row.div_ceil(align) * align;
+
+		let buffer = bcknd.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Offscreen Readback Buffer"),
+			size: padded_row as u64 * height as u64,
+			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+			mapped_at_creation: false,
+		});
+
+		let mut encoder = bcknd.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Offscreen Readback"),
+		});
+
+		encoder.copy_texture_to_buffer(
+			tex.as_image_copy(),
+			wgpu::ImageCopyBuffer {
+				buffer: &buffer,
+				layout: wgpu::ImageDataLayout {
+					offset: 0,
+					bytes_per_row: Some(padded_row),
+					rows_per_image: Some(height),
+				},
+			},
+			wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+		);
+
+		bcknd.queue.submit(std::iter::once(encoder.finish()));
+
+		let slice = buffer.slice(..);
+		slice.map_async(wgpu::MapMode::Read, |_| {});
+		bcknd.device.poll(wgpu::Maintain::Wait);
+
+		let mut data = Vec::with_capacity(width as usize * height as usize * TextureFormat::Rgba32F.pixel_size());
+
+		{
+			let mapped = slice.get_mapped_range();
+
+			for row in mapped.chunks(padded_row as usize) {
+				for channel in &row[..unpadded_row as usize] {
+					data.extend_from_slice(&(*channel as f32 / 255.0).to_ne_bytes());
+				}
+			}
+		}
+
+		buffer.unmap();
+
+		let mut out = Texture::new(width, height, TextureFormat::Rgba32F);
+		out.copy_from_slice(&data);
+
+		Some(out)
 	}
 }
 
@@ -161,6 +298,10 @@ impl Surface<WgpuBackend> for WgpuSurface {
 	fn reconfigure(&mut self, bcknd: &WgpuBackend, window_size: (u32, u32)) {
 		self.reconfigure(bcknd, window_size);
 	}
+
+	fn read_pixels(&mut self, bcknd: &mut WgpuBackend) -> Option<Texture> {
+		self.read_pixels(bcknd)
+	}
 }
 
 
@@ -202,13 +343,13 @@ impl<'a> View for WgpuView<'a> {
 	type B = WgpuBackend;
 
 	fn push(&mut self) {
-		self.state.push(self.state.last().unwrap().clone())
+		self.state.push(*self.get_state())
 	}
 
 	fn restore(&mut self) {
 		self.state.pop();
 
-		if self.state.len() == 0 {
+		if self.state.is_empty() {
 			self.state.push(WgpuViewState::new(self.window_size))
 		}
 	}
@@ -221,26 +362,39 @@ impl<'a> View for WgpuView<'a> {
 		self.get_state().size
 	}
 
+	fn viewport_pos(&self) -> (i32, i32) {
+		self.get_state().pos
+	}
+
+	fn set_viewport(&mut self, rect: Rect) {
+		let state = self.get_state_mut();
+		state.pos = rect.pos();
+		state.size = rect.size();
+	}
+
 	fn set_viewport_horizontal(&mut self, offset: u32, width: u32) {
 		let state = self.get_state_mut();
-		state.pos.0 += offset;
+		state.pos.0 += offset as i32;
 		state.size.0 = width;
 	}
 
 	fn set_viewport_vertical(&mut self, offset: u32, height: u32) {
 		let state = self.get_state_mut();
-		state.pos.1 += offset;
+		state.pos.1 += offset as i32;
 		state.size.1 = height;
 	}
 
 	fn apply_bounds(&mut self, bounds: Bounds) {
 		let state = self.get_state_mut();
+		let rect = Rect::new(state.pos, state.size).shrink(bounds);
 
-		state.pos.0 += bounds.left;
-		state.size.0 -= bounds.left + bounds.right;
+		state.pos = rect.pos();
+		state.size = rect.size();
+	}
 
-		state.pos.1 += bounds.top;
-		state.size.1 -= bounds.top + bounds.bottom;
+	fn set_clip(&mut self, rect: Rect) {
+		let state = self.get_state_mut();
+		state.clip = state.clip.intersect(&rect);
 	}
 
 	fn fill(&mut self, finish: Finish) {
@@ -249,13 +403,16 @@ impl<'a> View for WgpuView<'a> {
 			Finish::Color(c) => c,
 			_ => todo!() // TODO
 		};
-		self.surface.pipeline.queue_quad(self.bcknd, (state.pos.0 as i32, state.pos.1 as i32), state.size, color, None, None, self.surface.view.as_ref().unwrap()).unwrap()
+
+		if let Some((pos, size, _)) = clip_quad(state.pos, state.size, None, state.clip) {
+			self.surface.pipeline.queue_quad(self.bcknd, pos, size, color, None, None, self.surface.view.as_ref().unwrap()).unwrap()
+		}
 	}
 
 	fn draw_rect(&mut self, pos: (i32, i32), size: (u32, u32), color: Color, tex: Option<TextureId>, tex_offset: Option<(u32, u32)>) {
 		let state = self.get_state();
 
-		let pos = (state.pos.0 as i32 + pos.0 as i32, state.pos.1 as i32 + pos.1 as i32);
+		let pos = (state.pos.0 + pos.0, state.pos.1 + pos.1);
 
 		let tex_coords = if let Some(tex_offset) = tex_offset {
 			let from_x = tex_offset.0 as f32 / 1024.0;
@@ -268,6 +425,11 @@ impl<'a> View for WgpuView<'a> {
 			None
 		};
 
+		let (pos, size, tex_coords) = match clip_quad(pos, size, tex_coords, state.clip) {
+			Some(q) => q,
+			None => return,
+		};
+
 		self.surface.pipeline.queue_quad(
 			self.bcknd,
 			pos,
@@ -282,8 +444,7 @@ impl<'a> View for WgpuView<'a> {
 
 	fn submit(self) {
 		self.surface.pipeline.flush(self.surface.view.as_ref().unwrap(), self.bcknd).unwrap();
-		self.surface.tex.take().map(|t| t.present());
-		self.surface.view.take();
+		self.surface.present();
 	}
 
 	fn backend(&mut self) -> &mut Self::B {
@@ -292,10 +453,46 @@ impl<'a> View for WgpuView<'a> {
 }
 
 
+/// The position, size and texture coordinates of a quad.
+type Quad = ((i32, i32), (u32, u32), Option<TexCoords>);
+
+/// Cut off the parts of a quad outside of `clip`, adjusting its texture coordinates to match.
+/// 
+/// Returns [None] if nothing is left.
+fn clip_quad(pos: (i32, i32), size: (u32, u32), tex_coords: Option<TexCoords>, clip: Rect) -> Option<Quad> {
+	let rect = Rect::new(pos, size);
+	let clipped = rect.intersect(&clip);
+
+	if clipped.width == 0 || clipped.height == 0 {
+		return None;
+	}
+
+	if clipped == rect {
+		return Some((pos, size, tex_coords));
+	}
+
+	let (from, to) = match tex_coords {
+		Some(c) => (c.0, c.2),
+		None => ([0.0, 0.0], [1.0, 1.0]),
+	};
+
+	let lerp = |axis: usize, v: i32, start: i32, len: u32| from[axis] + (to[axis] - from[axis]) * (v - start) as f32 / len as f32;
+
+	let x0 = lerp(0, clipped.x, rect.x, rect.width);
+	let y0 = lerp(1, clipped.y, rect.y, rect.height);
+	let x1 = lerp(0, clipped.x + clipped.width as i32, rect.x, rect.width);
+	let y1 = lerp(1, clipped.y + clipped.height as i32, rect.y, rect.height);
+
+	Some((clipped.pos(), clipped.size(), Some(([x0, y0], [x0, y1], [x1, y1], [x1, y0]))))
+}
+
+
 #[derive(Debug, Clone, Copy)]
 pub struct WgpuViewState {
-	pos: (u32, u32),
+	pos: (i32, i32),
 	size: (u32, u32),
+	/// Nothing is drawn outside of this rectangle.
+	clip: Rect,
 }
 
 impl WgpuViewState {
@@ -303,8 +500,38 @@ impl WgpuViewState {
 		Self {
 			pos: (0, 0),
 			size: window_size,
+			clip: Rect::new((0, 0), window_size),
 		}
 	}
 }
 
 
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_clip_quad() {
+		let clip = Rect::new((2, 0), (4, 4));
+
+		// inside or outside of the clip rect
+		assert_eq!(clip_quad((2, 1), (2, 2), None, clip), Some(((2, 1), (2, 2), None)));
+		assert_eq!(clip_quad((6, 0), (2, 2), None, clip), None);
+
+		// the right half is left, with the texture coordinates to match
+		assert_eq!(
+			clip_quad((0, 0), (4, 4), None, clip),
+			Some(((2, 0), (2, 4), Some(([0.5, 0.0], [0.5, 1.0], [1.0, 1.0], [1.0, 0.0])))),
+		);
+
+		// only part of the texture is drawn to begin with
+		let tex_coords = ([0.0, 0.0], [0.0, 0.5], [0.5, 0.5], [0.5, 0.0]);
+
+		assert_eq!(
+			clip_quad((2, 2), (4, 4), Some(tex_coords), clip),
+			Some(((2, 2), (4, 2), Some(([0.0, 0.0], [0.0, 0.25], [0.5, 0.25], [0.5, 0.0])))),
+		);
+	}
+}
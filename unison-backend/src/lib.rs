@@ -6,6 +6,11 @@ pub trait Backend: Default {
 	type Surface: Surface<Self> + 'static;
 
 	fn create_surface(&self, window: &winit::window::Window) -> Self::Surface;
+	/// Create a [Surface] that is not bound to any window and renders into memory instead.
+	/// 
+	/// Its contents can be read back using [Surface::read_pixels].
+	/// Backends that cannot create empty surfaces may round a zero width or height up to 1.
+	fn create_offscreen_surface(&self, size: (u32, u32)) -> Self::Surface;
 	fn create_view<'a>(&'a mut self, surface: &'a mut Self::Surface) -> Self::View<'a>;
 
 	fn upload_texture(&mut self, tex: &Texture) -> TextureId;
@@ -26,13 +31,24 @@ pub trait View {
 	/// Get the current viewports size.
 	fn viewport_size(&self) -> (u32, u32);
 
+	/// Get the current viewports position within the window.
+	fn viewport_pos(&self) -> (i32, i32);
+
+	/// Set the current viewport to a rectangle in window coordinates.
+	fn set_viewport(&mut self, rect: Rect);
+
 	fn set_viewport_horizontal(&mut self, offset: u32, width: u32);
 
 	fn set_viewport_vertical(&mut self, offset: u32, width: u32);
 
-	/// Apply some [Bounds] to the current viewport.
+	/// Apply some [Bounds] to the current viewport, saturating at a size of zero.
 	fn apply_bounds(&mut self, bounds: Bounds);
 
+	/// Restrict all drawing to `rect` (in window coordinates) within the current clip rectangle.
+	/// 
+	/// Like the viewport, the clip rectangle is part of the state saved by [View::push].
+	fn set_clip(&mut self, rect: Rect);
+
 	/// Fill the current viewport with a [Finish].
 	fn fill(&mut self, finish: Finish);
 
@@ -44,4 +60,10 @@ pub trait View {
 
 pub trait Surface<B: Backend> {
 	fn reconfigure(&mut self, bcknd: &B, window_size: (u32, u32));
+
+	/// Read back the last frame submitted to this [Surface].
+	/// 
+	/// The colors are returned as they would be displayed (sRGB encoded).
+	/// Returns [None] if the surface cannot be read, which is the case for window surfaces.
+	fn read_pixels(&mut self, bcknd: &mut B) -> Option<Texture>;
 }
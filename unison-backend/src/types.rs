@@ -1,6 +1,6 @@
 
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Bounds {
 	pub top: u32,
 	pub left: u32,
@@ -15,18 +15,83 @@ impl Bounds {
 }
 
 
+/// An axis-aligned rectangle in window coordinates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rect {
+	pub x: i32,
+	pub y: i32,
+	pub width: u32,
+	pub height: u32,
+}
+
+impl Rect {
+	pub fn new(pos: (i32, i32), size: (u32, u32)) -> Self {
+		Self { x: pos.0, y: pos.1, width: size.0, height: size.1 }
+	}
+
+	pub fn pos(&self) -> (i32, i32) {
+		(self.x, self.y)
+	}
+
+	pub fn size(&self) -> (u32, u32) {
+		(self.width, self.height)
+	}
+
+	/// Shrink the rectangle by some [Bounds], saturating at a size of zero.
+	pub fn shrink(&self, bounds: Bounds) -> Rect {
+		Rect {
+			x: self.x + bounds.left as i32,
+			y: self.y + bounds.top as i32,
+			width: self.width.saturating_sub(bounds.left + bounds.right),
+			height: self.height.saturating_sub(bounds.top + bounds.bottom),
+		}
+	}
+
+	/// Grow the rectangle by some [Bounds].
+	pub fn expand(&self, bounds: Bounds) -> Rect {
+		Rect {
+			x: self.x - bounds.left as i32,
+			y: self.y - bounds.top as i32,
+			width: self.width + bounds.left + bounds.right,
+			height: self.height + bounds.top + bounds.bottom,
+		}
+	}
+
+	/// Get the area covered by both rectangles, which has a size of zero if they do not overlap.
+	pub fn intersect(&self, other: &Rect) -> Rect {
+		let x = self.x.max(other.x);
+		let y = self.y.max(other.y);
+		let right = (self.x as i64 + self.width as i64).min(other.x as i64 + other.width as i64);
+		let bottom = (self.y as i64 + self.height as i64).min(other.y as i64 + other.height as i64);
+
+		Rect {
+			x,
+			y,
+			width: (right - x as i64).max(0) as u32,
+			height: (bottom - y as i64).max(0) as u32,
+		}
+	}
+
+	/// Check if a point lies within the rectangle.
+	pub fn contains(&self, point: (f32, f32)) -> bool {
+		point.0 >= self.x as f32 && point.0 < self.x as f32 + self.width as f32 &&
+		point.1 >= self.y as f32 && point.1 < self.y as f32 + self.height as f32
+	}
+}
+
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color(pub f64, pub f64, pub f64, pub f64);
 
-impl Into<[f64; 4]> for Color {
-	fn into(self) -> [f64; 4] {
-		[self.0, self.1, self.2, self.3]
+impl From<Color> for [f64; 4] {
+	fn from(value: Color) -> Self {
+		[value.0, value.1, value.2, value.3]
 	}
 }
 
-impl Into<[f32; 4]> for Color {
-	fn into(self) -> [f32; 4] {
-		[self.0 as f32, self.1 as f32, self.2 as f32, self.3 as f32]
+impl From<Color> for [f32; 4] {
+	fn from(value: Color) -> Self {
+		[value.0 as f32, value.1 as f32, value.2 as f32, value.3 as f32]
 	}
 }
 
@@ -61,7 +126,7 @@ impl Texture {
 	pub fn new(width: u32, height: u32, format: TextureFormat) -> Self {
 		Self {
 			data: Vec::with_capacity(width as usize * height as usize * format.pixel_size()),
-			format: format,
+			format,
 			width,
 			height,
 		}
@@ -114,8 +179,8 @@ impl Texture {
 		let height = img.height();
 
 		let (data, format) = match img {
-			image::DynamicImage::ImageRgb32F(buf) => (Vec::from(buf.as_bytes()), TextureFormat::Rgba32F),
-			_ => (Vec::from(img.into_rgb32f().as_bytes()), TextureFormat::Rgba32F)
+			image::DynamicImage::ImageRgba32F(buf) => (Vec::from(buf.as_bytes()), TextureFormat::Rgba32F),
+			_ => (Vec::from(img.into_rgba32f().as_bytes()), TextureFormat::Rgba32F)
 		};
 
 		Self {
@@ -126,6 +191,18 @@ impl Texture {
 		}
 	}
 
+	/// Convert the [Texture] into an 8 bit [image::RgbaImage].
+	pub fn to_image(&self) -> image::RgbaImage {
+		let data = match self.format {
+			TextureFormat::Rgba32F => self.data
+				.chunks_exact(4)
+				.map(|c| (f32::from_ne_bytes([c[0], c[1], c[2], c[3]]).clamp(0.0, 1.0) * 255.0 + 0.5) as u8)
+				.collect(),
+		};
+
+		image::RgbaImage::from_raw(self.width, self.height, data).unwrap() // the data always has the right length
+	}
+
 	/// Try to load image form a slice.
 	pub fn from_bytes(data: &[u8]) -> image::ImageResult<Self> {
 		Ok(Self::from_image(image::load_from_memory(data)?))
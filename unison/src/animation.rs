@@ -0,0 +1,385 @@
+//! Animated [Value]s.
+//!
+//! An [Animated] value follows another [Value], but instead of jumping to a new value when it changes,
+//! it transitions to it over the duration of a [Transition].
+//! While a transition is running, the container that evaluated it is laid out or redrawn every frame.
+//! When a component is rebuilt, the animated values of its new [Layout] continue the transitions of the old one.
+//!
+//! Example:
+//! ```rust
+//! use unison::*;
+//! use std::time::Duration;
+//!
+//! let mut state = State::new();
+//! let focused = state.window_focused;
+//!
+//! let mut layout = Layout::new();
+//! layout.set_margin(
+//!     bind!(focused => if focused { Bounds::new(10, 10, 10, 10) } else { Bounds::default() })
+//!         .animate(Transition::new(Duration::from_millis(200), Easing::EaseOut))
+//! );
+//! ```
+
+use crate::*;
+
+use std::any::Any;
+use std::cell::Cell;
+use std::time::{ Duration, Instant };
+
+
+/// Values that can be interpolated linearly.
+pub trait Interpolate: Copy + PartialEq {
+	/// Get the value at `t` on the way from `self` (at 0.0) to `to` (at 1.0).
+	fn interpolate(&self, to: &Self, t: f64) -> Self;
+}
+
+impl Interpolate for u32 {
+	fn interpolate(&self, to: &Self, t: f64) -> Self {
+		(*self as f64 + (*to as f64 - *self as f64) * t).round() as u32
+	}
+}
+
+impl Interpolate for i32 {
+	fn interpolate(&self, to: &Self, t: f64) -> Self {
+		(*self as f64 + (*to as f64 - *self as f64) * t).round() as i32
+	}
+}
+
+impl Interpolate for f32 {
+	fn interpolate(&self, to: &Self, t: f64) -> Self {
+		self + (to - self) * t as f32
+	}
+}
+
+impl Interpolate for f64 {
+	fn interpolate(&self, to: &Self, t: f64) -> Self {
+		self + (to - self) * t
+	}
+}
+
+impl Interpolate for Bounds {
+	fn interpolate(&self, to: &Self, t: f64) -> Self {
+		Bounds {
+			top: self.top.interpolate(&to.top, t),
+			left: self.left.interpolate(&to.left, t),
+			bottom: self.bottom.interpolate(&to.bottom, t),
+			right: self.right.interpolate(&to.right, t),
+		}
+	}
+}
+
+impl Interpolate for Color {
+	fn interpolate(&self, to: &Self, t: f64) -> Self {
+		Color(
+			self.0.interpolate(&to.0, t),
+			self.1.interpolate(&to.1, t),
+			self.2.interpolate(&to.2, t),
+			self.3.interpolate(&to.3, t),
+		)
+	}
+}
+
+macro_rules! impl_tuple_interpolate {
+	( $( $name:ident ),* ) => {
+
+		impl< $( $name: Interpolate ),* > Interpolate for ($($name,)*) {
+			fn interpolate(&self, to: &Self, t: f64) -> Self {
+				paste::paste! {
+					#[allow(non_snake_case)]
+					let ( $( $name, )* ) = self;
+					#[allow(non_snake_case)]
+					let ( $( [<to_ $name>], )* ) = to;
+
+					( $( $name.interpolate([<to_ $name>], t), )* )
+				}
+			}
+		}
+
+	};
+}
+
+// Interpolate requires PartialEq, which std only implements for tuples of up to 12 elements
+impl_tuple_interpolate!(A);
+impl_tuple_interpolate!(A, B);
+impl_tuple_interpolate!(A, B, C);
+impl_tuple_interpolate!(A, B, C, D);
+impl_tuple_interpolate!(A, B, C, D, E);
+impl_tuple_interpolate!(A, B, C, D, E, F);
+impl_tuple_interpolate!(A, B, C, D, E, F, G);
+impl_tuple_interpolate!(A, B, C, D, E, F, G, H);
+impl_tuple_interpolate!(A, B, C, D, E, F, G, H, I);
+impl_tuple_interpolate!(A, B, C, D, E, F, G, H, I, J);
+impl_tuple_interpolate!(A, B, C, D, E, F, G, H, I, J, K);
+impl_tuple_interpolate!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+
+/// How the progress of a [Transition] maps to the interpolation between two values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+	Linear,
+	EaseIn,
+	#[default]
+	EaseOut,
+	EaseInOut,
+}
+
+impl Easing {
+	/// Map the linear progress `t` (between 0.0 and 1.0).
+	pub fn apply(&self, t: f64) -> f64 {
+		match self {
+			Self::Linear => t,
+			Self::EaseIn => t * t * t,
+			Self::EaseOut => 1.0 - (1.0 - t).powi(3),
+			Self::EaseInOut => if t < 0.5 {
+				4.0 * t * t * t
+			} else {
+				1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+			},
+		}
+	}
+}
+
+/// The duration and [Easing] of an animation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+	pub duration: Duration,
+	pub easing: Easing,
+}
+
+impl Transition {
+	pub fn new(duration: Duration, easing: Easing) -> Self {
+		Self { duration, easing }
+	}
+
+	/// Get the eased progress at `elapsed`.
+	fn progress(&self, elapsed: Duration) -> f64 {
+		if elapsed >= self.duration {
+			return 1.0;
+		}
+
+		self.easing.apply(elapsed.as_secs_f64() / self.duration.as_secs_f64())
+	}
+}
+
+
+#[derive(Clone, Copy)]
+struct Tween<T> {
+	from: T,
+	to: T,
+	start: Instant,
+}
+
+/// A [LazyValue] that transitions to the value of its target whenever it changes.
+///
+/// Create one using [Animate::animate].
+pub struct Animated<T: Interpolate> {
+	target: Value<T>,
+	transition: Transition,
+	tween: Cell<Option<Tween<T>>>,
+}
+
+impl<T: Interpolate> Animated<T> {
+	pub fn new(target: Value<T>, transition: Transition) -> Self {
+		Self { target, transition, tween: Cell::new(None) }
+	}
+}
+
+impl<T: Interpolate + 'static> LazyValue for Animated<T> {
+	type Output = T;
+
+	fn eval(&self, state: &State) -> Option<Self::Output> {
+		let target = self.target.eval(state)?;
+		let now = state.frame_time();
+
+		// the first value is not animated
+		let mut tween = self.tween.get().unwrap_or(Tween { from: target, to: target, start: now });
+
+		if tween.to != target {
+			// continue from wherever the running transition is
+			let current = tween.from.interpolate(&tween.to, self.transition.progress(now.saturating_duration_since(tween.start)));
+			tween = Tween { from: current, to: target, start: now };
+		}
+
+		self.tween.set(Some(tween));
+
+		let progress = match tween.from == tween.to {
+			true => 1.0,
+			false => self.transition.progress(now.saturating_duration_since(tween.start)),
+		};
+
+		if progress < 1.0 {
+			state.request_animation_frame();
+		}
+
+		Some(tween.from.interpolate(&tween.to, progress))
+	}
+
+	unsafe fn eval_unchecked(&self) -> Self::Output {
+		match &self.target {
+			Value::Const(v) => *v,
+			Value::Lazy(v) => v.eval_unchecked(),
+		}
+	}
+
+	fn animation(&self) -> Option<&dyn Any> {
+		Some(&self.tween)
+	}
+
+	fn continue_from(&self, old: &dyn LazyValue<Output = T>) {
+		// the new value has not been evaluated yet, it picks up where the old one is
+		if let Some(tween) = old.animation().and_then(|a| a.downcast_ref::<Cell<Option<Tween<T>>>>()) {
+			self.tween.set(tween.get());
+		}
+	}
+}
+
+impl<T: Interpolate + 'static> IntoValue for Animated<T> {
+	type Output = T;
+
+	fn into_value(self) -> Value<Self::Output> {
+		Value::Lazy(Box::new(self))
+	}
+}
+
+
+/// Turn anything that can be a [Value] into an [Animated] one.
+pub trait Animate: IntoValue + Sized where Self::Output: Interpolate {
+	fn animate(self, transition: Transition) -> Animated<Self::Output> {
+		Animated::new(self.into_value(), transition)
+	}
+}
+
+impl<T: IntoValue> Animate for T where T::Output: Interpolate {}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::container::ComponentTree;
+
+	#[test]
+	fn test_interpolate() {
+		assert_eq!(10u32.interpolate(&20, 0.5), 15);
+		assert_eq!(20u32.interpolate(&10, 0.25), 18);
+
+		assert_eq!(Bounds::new(0, 10, 0, 0).interpolate(&Bounds::new(10, 0, 0, 4), 0.5), Bounds::new(5, 5, 0, 2));
+		assert_eq!(Color(0.0, 0.0, 1.0, 1.0).interpolate(&Color(1.0, 0.0, 0.0, 1.0), 0.5), Color(0.5, 0.0, 0.5, 1.0));
+		assert_eq!((0u32, 1.0f32).interpolate(&(10, 2.0), 0.5), (5, 1.5));
+
+		assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+		assert_eq!(Easing::EaseOut.apply(1.0), 1.0);
+	}
+
+	#[test]
+	fn test_animated() {
+		let mut state = State::new();
+		let start = Instant::now();
+		let id = ContainerId::new();
+
+		let width = state.alloc(0u32);
+		let anim = bind!(width => width).animate(Transition::new(Duration::from_millis(100), Easing::Linear));
+
+		state.run_frame_callbacks(start);
+		assert_eq!(state.track(id, Phase::Layout, || anim.eval(&state)), Some(0));
+		assert!(!state.has_frame_callbacks());
+
+		state.set(width, 100);
+		state.take_dirty();
+
+		// the transition starts at the frame in which the change is seen
+		state.run_frame_callbacks(start + Duration::from_millis(10));
+		assert_eq!(state.track(id, Phase::Layout, || anim.eval(&state)), Some(0));
+		assert!(state.has_frame_callbacks());
+
+		// the container is laid out again in the next frame
+		state.run_frame_callbacks(start + Duration::from_millis(60));
+		assert_eq!(state.is_dirty(id), Some(Dirty::Layout));
+		assert_eq!(state.track(id, Phase::Layout, || anim.eval(&state)), Some(50));
+
+		// changing the target midway continues from the current value
+		state.set(width, 0);
+		state.run_frame_callbacks(start + Duration::from_millis(60));
+		assert_eq!(state.track(id, Phase::Layout, || anim.eval(&state)), Some(50));
+
+		state.run_frame_callbacks(start + Duration::from_millis(110));
+		assert_eq!(state.track(id, Phase::Layout, || anim.eval(&state)), Some(25));
+
+		state.run_frame_callbacks(start + Duration::from_millis(200));
+		state.take_dirty();
+		assert_eq!(state.track(id, Phase::Layout, || anim.eval(&state)), Some(0));
+
+		// settled
+		state.run_frame_callbacks(start + Duration::from_millis(210));
+		assert!(!state.has_frame_callbacks());
+		assert_eq!(state.is_dirty(id), None);
+	}
+
+	/// Moves to the right by an animated margin.
+	struct Indented {
+		indent: arena::Ref<u32>,
+	}
+
+	impl Component for Indented {
+		type Child = ();
+
+		fn build(&self, _: &mut State) -> Self::Child {}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let indent = self.indent;
+
+			let mut layout = Layout::new();
+			layout.set_margin(
+				bind!(indent => Bounds::new(0, indent, 0, 0))
+					.animate(Transition::new(Duration::from_millis(100), Easing::Linear))
+			);
+			layout
+		}
+	}
+
+	/// Rebuilds its child whenever `rebuilds` changes.
+	struct Parent {
+		indent: arena::Ref<u32>,
+		rebuilds: arena::Ref<u32>,
+	}
+
+	impl Component for Parent {
+		type Child = Indented;
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			state.get(self.rebuilds);
+			Indented { indent: self.indent }
+		}
+	}
+
+	#[test]
+	fn test_animated_rebuild() {
+		let mut state = State::new();
+		let mut font_state = FontState::new();
+		let start = Instant::now();
+
+		let (indent, rebuilds) = (state.alloc(0), state.alloc(0));
+		let mut tree = ComponentTree::new(Parent { indent, rebuilds }, &mut state);
+
+		let mut frame = |state: &mut State, ms| {
+			state.run_frame_callbacks(start + Duration::from_millis(ms));
+			let dirty = state.take_dirty();
+			tree.update(state, &dirty);
+			tree.layout(state, (200, 10), &mut font_state);
+
+			let child = *tree.hit_test((199.0, 5.0)).last().unwrap();
+			tree.get_event_handler(child).unwrap().boxes().padding_box.x
+		};
+
+		assert_eq!(frame(&mut state, 0), 0);
+
+		state.set(indent, 100);
+		assert_eq!(frame(&mut state, 10), 0);
+		assert_eq!(frame(&mut state, 60), 50);
+
+		// the new layout of the rebuilt child continues the transition
+		state.set(rebuilds, 1);
+		assert_eq!(frame(&mut state, 60), 50);
+		assert_eq!(frame(&mut state, 85), 75);
+		assert_eq!(frame(&mut state, 110), 100);
+	}
+}
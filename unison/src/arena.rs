@@ -2,16 +2,52 @@
 //! 
 //! To use it, create a new [Arena] and allocate values using [Arena::alloc].
 //! The returned [Ref]s can be accessed using [Arena::get] and [Arena::get_mut].
+//! 
+//! Single values can be freed using [Arena::free], their memory is then reused by later allocations
+//! of the same layout. Every allocation gets a slot with a generation counter,
+//! so [Ref]s to freed values reliably stay invalid, even when the memory has been reused.
+
+use std::collections::HashMap;
 
 
 /// A typeless, lifetimeless arena allocator that owns its values.
 /// 
-/// Currently does not drop its values.
+/// Values are dropped when they are freed, or when the arena is cleared or dropped.
 pub struct Arena<const BLOCK_SIZE: usize = 1024> {
 	arena_id: usize,
 	blocks: Vec<(std::alloc::Layout, *mut u8)>,
 	cur_block: usize,
 	offset: usize,
+	/// All allocations, in order of allocation.
+	slots: Vec<Slot>,
+	/// Slots whose value has been freed, by the layout of their memory.
+	free_slots: HashMap<std::alloc::Layout, Vec<u32>>,
+}
+
+/// The bookkeeping of a single allocation.
+struct Slot {
+	generation: u32,
+	alive: bool,
+	ptr: *mut u8,
+	drop: Option<DropFn>,
+}
+
+/// Drops a value in place, see [drop_value].
+type DropFn = unsafe fn(*mut u8);
+
+/// Get a new, unique arena id.
+fn next_arena_id() -> usize {
+	static ARENA_IDX: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+	ARENA_IDX.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Drop the value of type `T` at `ptr`.
+/// 
+/// # Safety
+/// `ptr` has to point to a valid value of type `T` which must not be used afterwards.
+unsafe fn drop_value<T>(ptr: *mut u8) {
+	std::ptr::drop_in_place(ptr as *mut T)
 }
 
 impl<const BLOCK_SIZE: usize> Arena<BLOCK_SIZE> {
@@ -24,25 +60,19 @@ impl<const BLOCK_SIZE: usize> Arena<BLOCK_SIZE> {
 	}
 
 	fn _new() -> Self {
-		static ARENA_IDX: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
-
-		let arena = Self {
-			arena_id: ARENA_IDX.load(std::sync::atomic::Ordering::Relaxed),
+		Self {
+			arena_id: next_arena_id(),
 			blocks: Vec::new(),
 			cur_block: 0,
 			offset: 0,
-		};
-
-		ARENA_IDX.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-
-		arena
+			slots: Vec::new(),
+			free_slots: HashMap::new(),
+		}
 	}
 
 	/// Create an [Arena] and allocate enough blocks to hold `n` bytes.
 	pub fn with_capacity(n: usize) -> Self {
-		let blocks = n / BLOCK_SIZE + if n % BLOCK_SIZE == 0 { 0 } else { 1 };
-
-		Self::with_blocks(blocks)
+		Self::with_blocks(n.div_ceil(BLOCK_SIZE))
 	}
 
 	/// Create an [Arena] and allocate `n` blocks
@@ -82,24 +112,60 @@ impl<const BLOCK_SIZE: usize> Arena<BLOCK_SIZE> {
 
 	/// Allocate a new value within the Arena and return a [Ref] to it.
 	/// 
-	/// When the current block is full and there is no free block left,
+	/// Reuses the memory of a freed value with the same layout if there is one.
+	/// Otherwise, when the current block is full and there is no free block left,
 	/// a new one will be allocated.
-	pub fn alloc<T>(&mut self, val: T) -> Ref<T> {
-		let layout = std::alloc::Layout::for_value(&val);
+	/// 
+	/// The value has to be `'static`, as it may be dropped at any later point
+	/// (when it is freed, or the Arena is cleared or dropped).
+	pub fn alloc<T: 'static>(&mut self, val: T) -> Ref<T> {
+		let layout = std::alloc::Layout::new::<T>();
+		let drop = if std::mem::needs_drop::<T>() { Some(drop_value::<T> as DropFn) } else { None };
+
+		let reused = self.free_slots.get_mut(&layout).and_then(|s| s.pop());
+
+		let slot = match reused {
+			Some(idx) => {
+				let slot = &mut self.slots[idx as usize];
+				slot.alive = true;
+				slot.drop = drop;
+				idx
+			},
+			None => {
+				let ptr = self.alloc_raw(layout);
+				self.slots.push(Slot { generation: 0, alive: true, ptr, drop });
+				(self.slots.len() - 1) as u32
+			}
+		};
 
-		if std::mem::size_of::<T>() >= BLOCK_SIZE {
+		let Slot { generation, ptr, .. } = self.slots[slot as usize];
+
+		// SAFETY:
+		// - there's enough space for the type at ptr, as it has been allocated with its layout
+		// - the pointer is aligned
+		// - a reused slot does not contain a value anymore
+		unsafe { std::ptr::write(ptr as *mut T, val) };
+
+		// SAFETY: the pointer is ensured not to be null by alloc_raw
+		Ref::new(self.arena_id, slot, generation, unsafe { std::ptr::NonNull::new_unchecked(ptr as *mut T) })
+	}
+
+	/// Reserve memory for the given layout.
+	fn alloc_raw(&mut self, layout: std::alloc::Layout) -> *mut u8 {
+		if layout.size() >= BLOCK_SIZE {
 			// allocate a personal block for val if its type needs more space than blocks can provide
 
 			let block = unsafe { std::alloc::alloc(layout) };
 
-			// SAFETY: we've just allocated space
-			unsafe { std::ptr::copy_nonoverlapping(&val, block as *mut T, 1) };
+			if block.is_null() {
+				panic!("Out of memory");
+			}
 
 			// insert it so that it is the second to last one
 			self.blocks.insert(self.blocks.len()-1, (layout, block));
 			self.cur_block += 1;
 
-			return Ref::new(self.arena_id, std::ptr::NonNull::new(block as *mut T).unwrap());
+			return block;
 		}
 
 		let (cur_block, align_offset) = {
@@ -131,54 +197,102 @@ impl<const BLOCK_SIZE: usize> Arena<BLOCK_SIZE> {
 
 
 		// TODO: ensure safety
-		let ptr = unsafe { cur_block.add(self.offset).add(align_offset) } as *mut T;
-
-		// SAFETY:
-		// - there's enough space in the block for the type
-		// - the pointer is aligned
-		unsafe { std::ptr::copy_nonoverlapping(&val, ptr, 1) };
+		let ptr = unsafe { cur_block.add(self.offset).add(align_offset) };
 
 		// TODO: ensure safety
 		self.offset += align_offset + layout.size();
 
-		// SAFETY: the pointer is ensured not to be null when allocating the block in [alloc_block]
-		Ref::new(self.arena_id, unsafe { std::ptr::NonNull::new_unchecked(ptr) })
+		ptr
+	}
+
+	/// Drop all values, in reverse order of allocation.
+	fn drop_values(&mut self) {
+		for slot in self.slots.drain(..).rev() {
+			if let (true, Some(drop)) = (slot.alive, slot.drop) {
+				// SAFETY: the value is alive and no longer accessible, as its slot is gone
+				unsafe { drop(slot.ptr) };
+			}
+		}
+
+		self.free_slots.clear();
+	}
+
+	/// Check if `r` points to a living value within this Arena.
+	pub fn contains<T: ?Sized>(&self, r: Ref<T>) -> bool {
+		self.contains_id(r.id())
+	}
+
+	/// Check if the value identified by `id` is still alive, see [Arena::contains].
+	pub fn contains_id(&self, id: RefId) -> bool {
+		id.arena_id == self.arena_id && self.slots.get(id.slot as usize)
+			.is_some_and(|s| s.alive && s.generation == id.generation)
 	}
 
 	/// Get a reference to a value within the Arena.
 	/// 
-	/// Returns [None] when the value is invalid (Arena has been cleared, does not belong to this Arena).
+	/// Returns [None] when the value is invalid (Arena has been cleared, value has been freed, does not belong to this Arena).
 	pub fn get<T>(&self, r: Ref<T>) -> Option<&T> {
-		if r.arena_id != self.arena_id {
+		if !self.contains(r) {
 			return None;
 		}
 
-		// SAFETY: as long as the arena_id is equal, the memory pointed to has not been deallocated
+		// SAFETY: as long as the slot is alive with the same generation, the value has not been dropped
 		Some(unsafe { r.ptr.as_ref() })
 	}
 
 	/// Get a mutable reference to a value within the Arena.
 	/// 
-	/// Returns [None] when the value is invalid (Arena has been cleared, does not belong to this Arena).
+	/// Returns [None] when the value is invalid (Arena has been cleared, value has been freed, does not belong to this Arena).
 	pub fn get_mut<T>(&mut self, mut r: Ref<T>) -> Option<&mut T> {
-		if r.arena_id != self.arena_id {
+		if !self.contains(r) {
 			return None;
 		}
 
-		// SAFETY: as long as the arena_id is equal, the memory pointed to has not been deallocated
+		// SAFETY: as long as the slot is alive with the same generation, the value has not been dropped
 		Some(unsafe { r.ptr.as_mut() })
 	}
 
-	/// Clear the arena, leaving the blocks allocated.
+	/// Remove a single value from the Arena and return it.
+	/// 
+	/// Its memory will be reused by the next allocation with the same layout,
+	/// `r` and all of its copies become invalid.
+	/// Returns [None] when the value is invalid.
+	pub fn free<T>(&mut self, r: Ref<T>) -> Option<T> {
+		if !self.contains(r) {
+			return None;
+		}
+
+		let slot = &mut self.slots[r.slot as usize];
+		slot.alive = false;
+		slot.generation = slot.generation.wrapping_add(1);
+
+		self.free_slots.entry(std::alloc::Layout::new::<T>()).or_default().push(r.slot);
+
+		// SAFETY: the value is alive and cannot be accessed through its slot anymore
+		Some(unsafe { std::ptr::read(r.ptr.as_ptr()) })
+	}
+
+	/// Clear the arena, dropping all values but leaving the blocks allocated.
+	/// 
+	/// All existing [Ref]s become invalid.
 	pub fn clear(&mut self) {
+		self.drop_values();
+
+		self.arena_id = next_arena_id();
 		self.cur_block = 0;
 		self.offset = 0;
 	}
 }
 
+impl<const BLOCK_SIZE: usize> Default for Arena<BLOCK_SIZE> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl<const BLOCK_SIZE: usize> Drop for Arena<BLOCK_SIZE> {
 	fn drop(&mut self) {
-		// TODO: implement dropping of values?
+		self.drop_values();
 
 		for block in &self.blocks {
 			// SAFETY: as long as self is alive, the memory pointed to has not been deallocated
@@ -192,13 +306,15 @@ impl<const BLOCK_SIZE: usize> Drop for Arena<BLOCK_SIZE> {
 #[derive(Debug)]
 pub struct Ref<T: ?Sized> {
 	arena_id: usize,
+	slot: u32,
+	generation: u32,
 	ptr: std::ptr::NonNull<T>,
 }
 
 impl<T: ?Sized> Ref<T> {
-	pub(self) fn new(arena_id: usize, ptr: std::ptr::NonNull<T>) -> Self {
+	pub(self) fn new(arena_id: usize, slot: u32, generation: u32, ptr: std::ptr::NonNull<T>) -> Self {
 		Self {
-			arena_id, ptr
+			arena_id, slot, generation, ptr
 		}
 	}
 
@@ -206,7 +322,7 @@ impl<T: ?Sized> Ref<T> {
 	/// 
 	/// Use with caution. For a safe variation, see: [Arena::get]
 	/// 
-	/// Safety:
+	/// # Safety
 	/// - the [Arena] this Ref belongs to has to be alive
 	/// - there must not be any mutable reference to the same value
 	pub unsafe fn get_unchecked(&self) -> &T {
@@ -217,7 +333,7 @@ impl<T: ?Sized> Ref<T> {
 	/// 
 	/// Use with caution. For a safe variation, see: [Arena::get_mut]
 	/// 
-	/// Safety:
+	/// # Safety
 	/// - the [Arena] this Ref belongs to has to be alive
 	/// - there must not be any mutable OR shared reference to the same value
 	pub unsafe fn get_mut_unchecked(&mut self) -> &mut T {
@@ -231,20 +347,45 @@ impl<T: ?Sized> Ref<T> {
 	pub fn as_ptr(&self) -> std::ptr::NonNull<T> {
 		self.ptr
 	}
+
+	/// Get an untyped identifier of the referenced value.
+	/// 
+	/// Values that reuse the memory of a freed value have a different id.
+	pub fn id(&self) -> RefId {
+		RefId {
+			arena_id: self.arena_id,
+			slot: self.slot,
+			generation: self.generation,
+		}
+	}
+}
+
+
+/// An untyped identifier of a value within an [Arena], see [Ref::id].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RefId {
+	arena_id: usize,
+	slot: u32,
+	generation: u32,
 }
 
 
 impl<T: ?Sized> Clone for Ref<T> {
 	fn clone(&self) -> Self {
-		Ref { arena_id: self.arena_id, ptr: self.ptr }
+		*self
 	}
 }
 
 impl<T: ?Sized> Copy for Ref<T> {}
 
+// SAFETY: a Ref can only be dereferenced through its Arena (or unsafely),
+// so passing it to another thread does not give that thread access to the value
+unsafe impl<T: ?Sized> Send for Ref<T> {}
+unsafe impl<T: ?Sized> Sync for Ref<T> {}
+
 impl<T: ?Sized> PartialEq for Ref<T> {
 	fn eq(&self, other: &Self) -> bool {
-		self.arena_id == other.arena_id && self.ptr == other.ptr
+		self.id() == other.id()
 	}
 }
 
@@ -252,8 +393,7 @@ impl<T: ?Sized> Eq for Ref<T> {}
 
 impl<T: ?Sized> std::hash::Hash for Ref<T> {
 	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-		state.write_usize(self.arena_id);
-		state.write_usize(self.ptr.as_ptr() as *mut () as usize);
+		self.id().hash(state)
 	}
 }
 
@@ -263,7 +403,12 @@ use crate::ComponentLike;
 
 impl<'a, T: ComponentLike + 'a> From<Ref<T>> for Ref<dyn ComponentLike + 'a> {
 	fn from(value: Ref<T>) -> Self {
-		Ref { arena_id: value.arena_id, ptr: value.ptr as std::ptr::NonNull<dyn ComponentLike> }
+		Ref {
+			arena_id: value.arena_id,
+			slot: value.slot,
+			generation: value.generation,
+			ptr: value.ptr as std::ptr::NonNull<dyn ComponentLike>,
+		}
 	}
 }
 
@@ -334,6 +479,68 @@ mod test {
 		test_auto_traits::<Arena>();
 	}
 
+	#[test]
+	fn test_free() {
+		let counter = std::rc::Rc::new(());
+
+		let mut arena = Arena::<32>::new();
+
+		let a = arena.alloc(1u64);
+		let b = arena.alloc(counter.clone());
+		let offset = arena.offset;
+
+		assert_eq!(arena.free(a), Some(1));
+		assert_eq!(arena.get(a), None);
+		assert_eq!(arena.free(a), None);
+
+		// the memory of `a` is reused, but `a` stays invalid
+		let c = arena.alloc(2u64);
+		assert_eq!(arena.offset, offset);
+		assert_eq!(c.as_ptr(), a.as_ptr());
+		assert_ne!(c.id(), a.id());
+		assert_eq!(arena.get(a), None);
+		assert_eq!(arena.get_mut(a), None);
+		assert_eq!(arena.get(c), Some(&2));
+
+		// values of other layouts do not reuse the slot
+		assert_eq!(arena.free(c), Some(2));
+		arena.alloc(3u32);
+		assert_ne!(arena.offset, offset);
+
+		drop(arena.free(b));
+		assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+
+		// freed values are not dropped again
+		drop(arena);
+		assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+	}
+
+	#[test]
+	fn test_drop_values() {
+		let counter = std::rc::Rc::new(());
+
+		let mut arena = Arena::<32>::new();
+
+		let s = arena.alloc(String::from("some text"));
+		arena.alloc(counter.clone());
+		arena.alloc(vec![counter.clone(); 3]);
+		// does not fit into a block
+		arena.alloc([Some(counter.clone()), None, None, None, None]);
+
+		assert_eq!(arena.get(s).map(String::as_str), Some("some text"));
+		assert_eq!(std::rc::Rc::strong_count(&counter), 6);
+
+		arena.clear();
+		assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+
+		// refs do not outlive a clear
+		assert_eq!(arena.get(s), None);
+
+		arena.alloc(counter.clone());
+		drop(arena);
+		assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+	}
+
 	// TODO: make it Send + Sync ?
 	fn test_auto_traits<T: Unpin>() {}
 }
@@ -1,25 +1,22 @@
 use crate::*;
 
+use std::rc::Rc;
+
 
 pub struct Label {
 	pub text: String,
 }
 
-impl Component for Label {
-	type Child = ();
-
-	fn build(&self, _: &mut State) -> Self::Child {
-		()
-	}
-
-	fn draw<'a, B: Backend>(&self, _: &State, view: &mut B::View<'a>, font_state: &mut FontState) {
+impl Label {
+	/// Lay out the text within the given size, [None] being unlimited.
+	fn shape(&self, font_state: &mut FontState, size: (Option<u32>, Option<u32>)) -> cosmic_text::Buffer {
 		let mut buf = cosmic_text::Buffer::new(&mut font_state.font_system, cosmic_text::Metrics { font_size: 16.0, line_height: 16.0 });
 
 		{
 			let mut buf = buf.borrow_with(&mut font_state.font_system);
 
-			let s = view.viewport_size();
-			buf.set_size(s.0 as f32, s.1 as f32);
+			let limit = |s: Option<u32>| s.map_or(f32::MAX, |s| s as f32);
+			buf.set_size(limit(size.0), limit(size.1));
 
 			buf.set_text(
 				&self.text,
@@ -31,6 +28,28 @@ impl Component for Label {
 			buf.shape_until_scroll();
 		}
 
+		buf
+	}
+}
+
+impl Component for Label {
+	type Child = ();
+
+	fn build(&self, _: &mut State) -> Self::Child {}
+
+	fn measure(&self, _: &State, available_size: (Option<u32>, Option<u32>), font_state: &mut FontState) -> (u32, u32) {
+		let buf = self.shape(font_state, available_size);
+
+		let (width, lines) = buf.layout_runs()
+			.fold((0.0f32, 0), |(width, lines), run| (width.max(run.line_w), lines + 1));
+
+		(width.ceil() as u32, (lines as f32 * buf.metrics().line_height).ceil() as u32)
+	}
+
+	fn draw<'a, B: Backend>(&self, _: &State, view: &mut B::View<'a>, font_state: &mut FontState) {
+		let s = view.viewport_size();
+		let buf = self.shape(font_state, (Some(s.0), Some(s.1)));
+
 		for line in buf.layout_runs() {
 			let line_y = line.line_y as i32;
 
@@ -39,7 +58,7 @@ impl Component for Label {
 
 				let fid = font_state.ensure_font(
 					glyph.cache_key.font_id,
-					unsafe { std::mem::transmute(glyph.cache_key.font_size_bits) }, view.backend());
+					f32::from_bits(glyph.cache_key.font_size_bits), view.backend());
 				let font = font_state.get_font::<B>(fid);
 
 
@@ -54,7 +73,7 @@ impl Component for Label {
 					};
 
 					// view.draw_rect(
-					// 	(glyph.x_int + g.left, line_y + glyph.y_int as i32 - g.top),
+					// 	(glyph.x_int + g.left, line_y + glyph.y_int - g.top),
 					// 	(g.width, g.height),
 					// 	Color(1.0, 0.0, 1.0, 0.2),
 					// 	None,
@@ -64,7 +83,7 @@ impl Component for Label {
 					let (new_x, _) = cosmic_text::SubpixelBin::new(glyph.x);
 
 					view.draw_rect(
-						(new_x + g.left, line_y + glyph.y_int as i32 - g.top),
+						(new_x + g.left, line_y + glyph.y_int - g.top),
 						(g.width, g.height),
 						color,
 						Some(tex_id),
@@ -75,3 +94,210 @@ impl Component for Label {
 		}
 	}
 }
+
+
+/// The values behind a [ScrollView], allocated once so that they survive when the view is rebuilt.
+#[derive(Clone, Copy)]
+pub struct ScrollState {
+	/// How far the content is scrolled, in pixels.
+	/// Setting it scrolls the content, see [State::scroll_extent] for the valid range.
+	pub offset: arena::Ref<(f32, f32)>,
+	/// The last position of the pointer or touch that is dragging the content.
+	drag: arena::Ref<Option<(f32, f32)>>,
+}
+
+impl ScrollState {
+	pub fn new(state: &mut State) -> Self {
+		Self {
+			offset: state.alloc((0.0, 0.0)),
+			drag: state.alloc(None),
+		}
+	}
+}
+
+
+/// Shows content larger than itself, scrolled by an offset and cut off at its edges.
+/// 
+/// The content is laid out at its intrinsic size and can be scrolled using the mouse wheel,
+/// the touchpad or by dragging it.
+pub struct ScrollView<C: Component> {
+	pub offset: arena::Ref<(f32, f32)>,
+	/// Whether scrollbars are shown while the content does not fit.
+	pub scrollbars: bool,
+	content: Rc<dyn Fn(&mut State) -> C>,
+	drag: arena::Ref<Option<(f32, f32)>>,
+}
+
+impl<C: Component> ScrollView<C> {
+	/// Create a view that scrolls the component built by `content`.
+	pub fn new<F: Fn(&mut State) -> C + 'static>(scroll: ScrollState, content: F) -> Self {
+		Self {
+			offset: scroll.offset,
+			scrollbars: true,
+			content: Rc::new(content),
+			drag: scroll.drag,
+		}
+	}
+
+	pub fn without_scrollbars(self) -> Self {
+		Self { scrollbars: false, ..self }
+	}
+
+	/// Move the offset by `delta` within the valid range, returning whether it changed.
+	fn scroll_by(&self, state: &mut State, delta: (f32, f32)) -> bool {
+		let extent = state.scroll_extent(self.offset);
+		let old = state.get(self.offset).copied().unwrap_or_default();
+
+		let new = (
+			(old.0 + delta.0).clamp(0.0, extent.0),
+			(old.1 + delta.1).clamp(0.0, extent.1),
+		);
+
+		state.set(self.offset, new);
+		new != old
+	}
+
+	/// Scroll by the distance the drag moved to `pos`, if the content is being dragged.
+	fn drag_to(&self, state: &mut State, pos: (f32, f32)) -> bool {
+		match state.get(self.drag).copied().flatten() {
+			Some(last) => {
+				state.set(self.drag, Some(pos));
+				self.scroll_by(state, (last.0 - pos.0, last.1 - pos.1));
+				true
+			},
+			None => false,
+		}
+	}
+}
+
+impl<C: Component> Component for ScrollView<C> {
+	type Child = (ScrollContent<C>, Scrollbar, Scrollbar);
+
+	fn build(&self, _: &mut State) -> Self::Child {
+		let scrollbar = |orientation| Scrollbar { offset: self.offset, orientation, visible: self.scrollbars };
+
+		(
+			ScrollContent { offset: self.offset, content: self.content.clone() },
+			scrollbar(Orientation::Vertical),
+			scrollbar(Orientation::Horizontal),
+		)
+	}
+
+	fn on_event(&self, event: &InputEvent, state: &mut State) -> bool {
+		match *event {
+			// the wheel moves the content in the opposite direction
+			InputEvent::Scroll { delta, .. } => self.scroll_by(state, (-delta.0, -delta.1)),
+
+			InputEvent::PointerPressed { pos, button: MouseButton::Left } |
+			InputEvent::Touch { phase: TouchPhase::Started, pos, .. } => {
+				state.set(self.drag, Some(pos));
+				true
+			},
+			InputEvent::PointerMoved { pos } |
+			InputEvent::Touch { phase: TouchPhase::Moved, pos, .. } => self.drag_to(state, pos),
+
+			InputEvent::PointerReleased { button: MouseButton::Left, .. } |
+			InputEvent::Touch { phase: TouchPhase::Ended | TouchPhase::Cancelled, .. } => {
+				let dragging = state.get(self.drag).is_some_and(|d| d.is_some());
+				state.set(self.drag, None);
+				dragging
+			},
+			InputEvent::PointerLeft => {
+				state.set(self.drag, None);
+				false
+			},
+
+			_ => false,
+		}
+	}
+}
+
+/// The scrolled part of a [ScrollView].
+pub struct ScrollContent<C: Component> {
+	offset: arena::Ref<(f32, f32)>,
+	content: Rc<dyn Fn(&mut State) -> C>,
+}
+
+impl<C: Component> Component for ScrollContent<C> {
+	type Child = C;
+
+	fn build(&self, state: &mut State) -> Self::Child {
+		(self.content)(state)
+	}
+
+	fn layout(&self, _: &mut State) -> Layout {
+		let mut layout = Layout::new();
+		layout.set_overflow(Overflow::Scroll(self.offset));
+		layout.set_stack_orientation(Orientation::Vertical);
+		layout.set_align_items(Align::Start);
+		layout
+	}
+}
+
+/// Shows how far a [ScrollView] is scrolled along one axis.
+pub struct Scrollbar {
+	pub offset: arena::Ref<(f32, f32)>,
+	pub orientation: Orientation,
+	pub visible: bool,
+}
+
+impl Scrollbar {
+	/// The thickness of the bar.
+	const WIDTH: u32 = 6;
+	/// The minimum length of the thumb.
+	const MIN_THUMB: f32 = 16.0;
+}
+
+impl Component for Scrollbar {
+	type Child = ();
+
+	fn build(&self, _: &mut State) -> Self::Child {}
+
+	fn layout(&self, _: &mut State) -> Layout {
+		let mut layout = Layout::new();
+		layout.set_position(Position::Absolute);
+
+		match self.orientation {
+			Orientation::Vertical => {
+				layout.set_top(Some(0));
+				layout.set_bottom(Some(0));
+				layout.set_right(Some(0));
+				layout.set_width(Length::Px(Self::WIDTH));
+			},
+			Orientation::Horizontal => {
+				layout.set_left(Some(0));
+				layout.set_right(Some(0));
+				layout.set_bottom(Some(0));
+				layout.set_height(Length::Px(Self::WIDTH));
+			},
+		}
+
+		layout
+	}
+
+	fn draw<'a, B: Backend>(&self, state: &State, view: &mut B::View<'a>, _: &mut FontState) {
+		let extent = state.scroll_extent(self.offset);
+		let offset = state.get(self.offset).copied().unwrap_or_default();
+		let size = view.viewport_size();
+
+		let (extent, offset, length) = match self.orientation {
+			Orientation::Vertical => (extent.1, offset.1, size.1 as f32),
+			Orientation::Horizontal => (extent.0, offset.0, size.0 as f32),
+		};
+
+		if !self.visible || extent <= 0.0 {
+			return;
+		}
+
+		// the thumb is to the track what the view is to the content
+		let thumb = (length * length / (length + extent)).max(Self::MIN_THUMB).min(length);
+		let pos = (offset.clamp(0.0, extent) / extent * (length - thumb)).round() as i32;
+
+		let color = Color(0.5, 0.5, 0.5, 0.8);
+
+		match self.orientation {
+			Orientation::Vertical => view.draw_rect((0, pos), (Self::WIDTH, thumb as u32), color, None, None),
+			Orientation::Horizontal => view.draw_rect((pos, 0), (thumb as u32, Self::WIDTH), color, None, None),
+		}
+	}
+}
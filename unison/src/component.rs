@@ -1,14 +1,49 @@
 use crate::*;
 
 
-pub trait Component {
+pub trait Component: 'static {
 	#![allow(unused_variables)]
 
+	/// The children created by [Component::build].
+	/// 
+	/// Either a single component, `()`, a [Vec], an [Option] or an [Either] of components,
+	/// or a tuple of any of these, e.g. `(Header, Option<Footer>)`.
 	type Child: ComponentLike + container::Containable;
 
+	/// Create the children of this component.
+	/// 
+	/// Runs again whenever a value read through the [State] changes,
+	/// the new children are then reconciled with the existing ones, see [Component::should_rebuild].
 	fn build(&self, state: &mut State) -> Self::Child;
 	fn draw<'a, B: Backend>(&self, state: &State, view: &mut B::View<'a>, font_state: &mut FontState) {}
 	fn layout(&self, state: &mut State) -> Layout { Layout::default() }
+
+	/// Get the size of the content of a component without children.
+	/// 
+	/// Called by the layout pass for components whose size is not fixed by their [Layout].
+	/// `available_size` limits the size along each axis, [None] means unlimited.
+	fn measure(&self, state: &State, available_size: (Option<u32>, Option<u32>), font_state: &mut FontState) -> (u32, u32) { (0, 0) }
+
+	/// Handle an [InputEvent].
+	/// 
+	/// Pointer events are passed to the components under the pointer, starting with the innermost one,
+	/// keyboard events to the focused component.
+	/// Return `true` to mark the event as handled, stopping it from being passed on to the parents.
+	/// 
+	/// A component that handles a [InputEvent::PointerPressed] gains focus.
+	fn on_event(&self, event: &InputEvent, state: &mut State) -> bool { false }
+
+	/// Check whether this component has to be rebuilt when it replaces `old`
+	/// after its parent has been rebuilt.
+	/// 
+	/// Returning `false` keeps `old` along with its children.
+	fn should_rebuild(&self, old: &Self) -> bool { true }
+
+	/// Identify this component among its siblings in a [Vec] or [Option].
+	/// 
+	/// When rebuilding, existing containers are reused for components with the same key,
+	/// components without a key reuse the container at the same position.
+	fn key(&self) -> Option<u64> { None }
 }
 
 
@@ -30,14 +65,63 @@ macro_rules! impl_get_set {
 }
 
 
+/// How a component is laid out within its parent, and how it lays out its children.
+/// 
+/// The children are laid out like in a CSS flexbox or grid, see [Layout::set_display].
 pub struct Layout {
+	display: Value<Display>,
+
+	/// How much the component grows, relative to its siblings.
 	flex: Value<u32>,
+	/// How much the component shrinks, relative to its siblings.
+	flex_shrink: Value<u32>,
+	/// The size along the main axis before growing or shrinking.
+	flex_basis: Value<Length>,
+
+	width: Value<Length>,
+	height: Value<Length>,
+	min_width: Value<Length>,
+	min_height: Value<Length>,
+	max_width: Value<Length>,
+	max_height: Value<Length>,
 
 	margin: Value<Bounds>,
 	padding: Value<Bounds>,
 
+	position: Value<Position>,
+	/// Offsets from the edges of the area the component is positioned in, [None] being unset.
+	left: Value<Option<i32>>,
+	top: Value<Option<i32>>,
+	right: Value<Option<i32>>,
+	bottom: Value<Option<i32>>,
+	overflow: Value<Overflow>,
+	/// The layer the component and its children are drawn in, [None] being the layer of its parent.
+	/// Higher layers are drawn on top of lower ones and receive input first.
+	z_index: Value<Option<i32>>,
+
+	/// The main axis of the children.
 	stack_orientation: Value<Orientation>,
+	/// Space between the children along the main axis.
 	stack_spacing: Value<u32>,
+	/// Space between the lines of wrapped children.
+	line_spacing: Value<u32>,
+	flex_wrap: Value<FlexWrap>,
+
+	justify_content: Value<JustifyContent>,
+	align_items: Value<Align>,
+	/// Overrides the parent's [Layout::set_align_items] for this component.
+	align_self: Value<Option<Align>>,
+
+	grid_columns: Value<Tracks>,
+	grid_rows: Value<Tracks>,
+	/// Space between the columns of a grid.
+	column_spacing: Value<u32>,
+	/// Space between the rows of a grid.
+	row_spacing: Value<u32>,
+	/// The columns the component occupies within its parent's grid.
+	grid_column: Value<GridPlacement>,
+	/// The rows the component occupies within its parent's grid.
+	grid_row: Value<GridPlacement>,
 }
 
 impl Layout {
@@ -46,12 +130,64 @@ impl Layout {
 	}
 
 
+	impl_get_set!(display, Display);
+
 	impl_get_set!(flex, u32);
+	impl_get_set!(flex_shrink, u32);
+	impl_get_set!(flex_basis, Length);
+
+	impl_get_set!(width, Length);
+	impl_get_set!(height, Length);
+	impl_get_set!(min_width, Length);
+	impl_get_set!(min_height, Length);
+	impl_get_set!(max_width, Length);
+	impl_get_set!(max_height, Length);
+
 	impl_get_set!(margin, Bounds);
 	impl_get_set!(padding, Bounds);
 
+	impl_get_set!(position, Position);
+	impl_get_set!(left, Option<i32>);
+	impl_get_set!(top, Option<i32>);
+	impl_get_set!(right, Option<i32>);
+	impl_get_set!(bottom, Option<i32>);
+	impl_get_set!(z_index, Option<i32>);
+	impl_get_set!(overflow, Overflow);
+
 	impl_get_set!(stack_orientation, Orientation);
 	impl_get_set!(stack_spacing, u32);
+	impl_get_set!(line_spacing, u32);
+	impl_get_set!(flex_wrap, FlexWrap);
+
+	impl_get_set!(justify_content, JustifyContent);
+	impl_get_set!(align_items, Align);
+	impl_get_set!(align_self, Option<Align>);
+
+	impl_get_set!(grid_columns, Tracks);
+	impl_get_set!(grid_rows, Tracks);
+	impl_get_set!(column_spacing, u32);
+	impl_get_set!(row_spacing, u32);
+	impl_get_set!(grid_column, GridPlacement);
+	impl_get_set!(grid_row, GridPlacement);
+
+	/// Continue the running animations of `old`, which this layout replaces.
+	pub(crate) fn continue_animations(&self, old: &Layout) {
+		macro_rules! continue_from {
+			( $( $name:ident ),* ) => {
+				$( self.$name.continue_from(&old.$name); )*
+			};
+		}
+
+		continue_from!(
+			display, flex, flex_shrink, flex_basis,
+			width, height, min_width, min_height, max_width, max_height,
+			margin, padding,
+			position, left, top, right, bottom, z_index, overflow,
+			stack_orientation, stack_spacing, line_spacing, flex_wrap,
+			justify_content, align_items, align_self,
+			grid_columns, grid_rows, column_spacing, row_spacing, grid_column, grid_row
+		);
+	}
 }
 
 
@@ -60,13 +196,45 @@ impl Layout {
 impl Default for Layout {
 	fn default() -> Self {
 		Self {
+			display: Display::default().into_value(),
+
 			flex: 1.into_value(),
+			flex_shrink: 1.into_value(),
+			flex_basis: Length::Auto.into_value(),
+
+			width: Length::Auto.into_value(),
+			height: Length::Auto.into_value(),
+			min_width: Length::Auto.into_value(),
+			min_height: Length::Auto.into_value(),
+			max_width: Length::Auto.into_value(),
+			max_height: Length::Auto.into_value(),
 
 			margin: Bounds::new(0, 0, 0, 0).into_value(),
 			padding: Bounds::new(0, 0, 0, 0).into_value(),
 
+			position: Position::default().into_value(),
+			left: None.into_value(),
+			top: None.into_value(),
+			right: None.into_value(),
+			bottom: None.into_value(),
+			z_index: None.into_value(),
+			overflow: Overflow::default().into_value(),
+
 			stack_orientation: Orientation::default().into_value(),
 			stack_spacing: 0.into_value(),
+			line_spacing: 0.into_value(),
+			flex_wrap: FlexWrap::default().into_value(),
+
+			justify_content: JustifyContent::default().into_value(),
+			align_items: Align::default().into_value(),
+			align_self: None.into_value(),
+
+			grid_columns: Tracks::default().into_value(),
+			grid_rows: Tracks::default().into_value(),
+			column_spacing: 0.into_value(),
+			row_spacing: 0.into_value(),
+			grid_column: GridPlacement::default().into_value(),
+			grid_row: GridPlacement::default().into_value(),
 		}
 	}
 }
@@ -92,10 +260,30 @@ impl ComponentLike for () {
 	
 }
 
+impl<T: Component> ComponentLike for Vec<T> {
+
+}
+
+impl<T: Component> ComponentLike for Option<T> {
+
+}
+
+impl<A: Component, B: Component> ComponentLike for Either<A, B> {
+
+}
+
+
+/// One of two components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<A, B> {
+	Left(A),
+	Right(B),
+}
+
 
 macro_rules! impl_tuple_component {
 	($($name:ident),*) => {
-		impl< $($name: Component),* > ComponentLike for ($($name,)*) {
+		impl< $($name: ComponentLike),* > ComponentLike for ($($name,)*) {
 
 		}
 	};
@@ -1,44 +1,474 @@
 use crate::*;
+use crate::layout::{ LayoutEngine, Measure };
+
+use std::collections::HashMap;
 
 
 /// A container that may or may not contain itself.
 pub struct ComponentContainer<T: Component> {
+	id: ContainerId,
 	component: T,
 	child: <T::Child as Containable>::Container,
 	pub layout: Layout,
+	/// The result of the last layout pass.
+	boxes: LayoutBoxes,
+	/// The node of the current layout pass.
+	node: Option<taffy::NodeId>,
+	/// The layer set by the layout, see [Layout::set_z_index].
+	z_index: Option<i32>,
+	/// The layer the container is drawn in, inherited from its parent unless set by the layout.
+	layer: i32,
+	/// The layers of the container and all of its descendants, see [ContainerLike::draw].
+	layers: Layers,
+	/// Whether any descendant is positioned, so that it might lie outside of the padding box.
+	positioned: bool,
+	/// Set by the layout, see [Layout::set_overflow].
+	overflow: Overflow,
+	/// The scroll offset read by the last layout pass.
+	scroll_offset: (f32, f32),
 }
 
 impl<T: Component> ComponentContainer<T> {
 	pub fn new(component: T, state: &mut State) -> Self {
-		let child = component.build(state);
-		let layout = component.layout(state);
+		let id = ContainerId::new();
+
+		state.scoped_mut(id, |state| {
+			let child = state.track_mut(id, Phase::Build, |state| state.with_owner(id, |state| component.build(state)));
+			let layout = component.layout(state);
+
+			Self {
+				id,
+				component,
+				child: child.contain(state),
+				layout,
+				boxes: LayoutBoxes::default(),
+				node: None,
+				z_index: None,
+				layer: 0,
+				layers: Layers::new(),
+				positioned: false,
+				overflow: Overflow::Visible,
+				scroll_offset: (0.0, 0.0),
+			}
+		})
+	}
+
+	/// Build the component again and reconcile the new children with the existing ones.
+	pub fn rebuild(&mut self, state: &mut State, dirty: &HashMap<ContainerId, Dirty>) {
+		let id = self.id;
+
+		state.scoped_mut(id, |state| {
+			// the component provides its values and subscribes again
+			state.remove_provided(id);
+			state.event_state.remove_owned_subscriptions(id);
+
+			let child = state.track_mut(id, Phase::Build, |state| state.with_owner(id, |state| self.component.build(state)));
+			child.reconcile(&mut self.child, state, dirty);
+		})
+	}
+
+	pub fn id(&self) -> ContainerId {
+		self.id
+	}
+
+	/// Get the result of the last layout pass.
+	pub fn boxes(&self) -> LayoutBoxes {
+		self.boxes
+	}
+}
+
+
+/// The absolute rectangles of a container, as computed by the layout pass.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LayoutBoxes {
+	/// The area assigned by the parent, including the margin.
+	pub margin_box: Rect,
+	/// The area the component itself draws into, including the padding.
+	pub padding_box: Rect,
+	/// The area the children are laid out in.
+	pub content_box: Rect,
+}
+
+impl LayoutBoxes {
+	pub fn new(margin_box: Rect, margin: Bounds, padding: Bounds) -> Self {
+		let padding_box = margin_box.shrink(margin);
 
 		Self {
-			component,
-			child: child.contain(state),
-			layout,
+			margin_box,
+			padding_box,
+			content_box: padding_box.shrink(padding),
 		}
 	}
 }
 
 
+/// A set of layers in ascending order, see [Layout::set_z_index].
+pub type Layers = smallvec::SmallVec<[i32; 2]>;
+
+fn add_layer(layers: &mut Layers, layer: i32) {
+	if let Err(i) = layers.binary_search(&layer) {
+		layers.insert(i, layer);
+	}
+}
+
+
 /// For types that may or may not be a [Container].
 pub trait ContainerLike {
-	fn draw<'a, B: Backend>(&self, state: &State, parent_layout: &Layout, view: &mut B::View<'a>, font_state: &mut FontState);
+	/// Add a layout node for each contained container to `engine` and push them onto `nodes`.
+	/// 
+	/// Returns whether any contained container or one of their descendants is positioned, see [Position].
+	fn layout_nodes(&mut self, state: &State, engine: &mut LayoutEngine, nodes: &mut Vec<taffy::NodeId>) -> bool;
+
+	/// Store the geometry computed by `engine`, `origin` being the position of the parent's padding box.
+	/// 
+	/// `layer` is the layer of the parent, see [Layout::set_z_index].
+	/// The layers of the contained containers and their descendants are added to `layers`.
+	fn apply_layout(&mut self, engine: &mut LayoutEngine, origin: (i32, i32), layer: i32, layers: &mut Layers);
+
+	/// Collect the contained containers by their id, so that the engine can measure them, see [Component::measure].
+	fn collect_measures<'a>(&'a self, measures: &mut HashMap<ContainerId, &'a dyn Measure>);
+
+	/// Draw the contained containers within `layer`, using the geometry computed by the last layout pass.
+	fn draw<'a, B: Backend>(&self, state: &State, view: &mut B::View<'a>, font_state: &mut FontState, layer: i32);
+
+	/// Push all containers under `pos` onto `path`, outermost first.
+	/// 
+	/// `clipped` are the layers in which `pos` lies outside of a clipping ancestor, see [Overflow].
+	fn hit_test(&mut self, pos: (f32, f32), tree_idx: usize, clipped: &mut Layers, path: &mut Vec<EventHandlerRef>);
+
+	/// Rebuild all contained containers that are marked as [Dirty::Rebuild].
+	fn update(&mut self, state: &mut State, dirty: &HashMap<ContainerId, Dirty>);
+
+	/// Called when all contained containers are about to be removed from their tree.
+	fn unmount(&mut self, state: &mut State);
 }
 
 impl<T: Component> ContainerLike for ComponentContainer<T> {
-	fn draw<'a, B: Backend>(&self, state: &State, _parent_layout: &Layout, view: &mut B::View<'a>, font_state: &mut FontState) {
-		view.apply_bounds(self.layout.get_margin(state).unwrap()); // TODO
-		self.component.draw::<B>(state, view, font_state);
+	fn layout_nodes(&mut self, state: &State, engine: &mut LayoutEngine, nodes: &mut Vec<taffy::NodeId>) -> bool {
+		state.scoped(self.id, || {
+			let (style, position, z_index, overflow) = state.track(self.id, Phase::Layout, || {
+				let overflow = self.layout.get_overflow(state).unwrap_or_default();
+
+				// scrolling moves the children, so they have to be laid out again
+				if let Overflow::Scroll(offset) = overflow {
+					self.scroll_offset = state.get(offset).copied().unwrap_or_default();
+				}
+
+				(
+					self.layout.to_style(state),
+					self.layout.get_position(state).unwrap_or_default(),
+					self.layout.get_z_index(state).flatten(),
+					overflow,
+				)
+			});
+
+			let mut children = Vec::new();
+			self.positioned = self.child.layout_nodes(state, engine, &mut children);
+
+			// moved by its offsets or not laid out with its siblings at all
+			let positioned = position != Position::Relative || [style.inset.left, style.inset.right, style.inset.top, style.inset.bottom]
+				.iter().any(|inset| *inset != taffy::LengthPercentageAuto::Auto);
+
+			let node = engine.add_node(style, &children, self.id);
+			self.node = Some(node);
+			self.z_index = z_index;
+			self.overflow = overflow;
+
+			// fixed containers are laid out within the window instead of their parent
+			match position {
+				Position::Fixed => engine.add_fixed(node),
+				_ => nodes.push(node),
+			}
+
+			positioned || self.positioned
+		})
+	}
+
+	fn apply_layout(&mut self, engine: &mut LayoutEngine, origin: (i32, i32), layer: i32, layers: &mut Layers) {
+		if let Some(node) = self.node.take() {
+			self.boxes = engine.boxes(node, origin);
+			self.layer = self.z_index.unwrap_or(layer);
+
+			self.layers.clear();
+			add_layer(&mut self.layers, self.layer);
+
+			let mut child_origin = self.boxes.padding_box.pos();
+
+			if let Overflow::Scroll(offset) = self.overflow {
+				// the content stays in view, even if the offset is out of range
+				let extent = engine.scroll_extent(node, offset.id());
+				child_origin.0 -= self.scroll_offset.0.clamp(0.0, extent.0).round() as i32;
+				child_origin.1 -= self.scroll_offset.1.clamp(0.0, extent.1).round() as i32;
+			}
+
+			self.child.apply_layout(engine, child_origin, self.layer, &mut self.layers);
+
+			for layer in &self.layers {
+				add_layer(layers, *layer);
+			}
+		}
+	}
+
+	fn collect_measures<'a>(&'a self, measures: &mut HashMap<ContainerId, &'a dyn Measure>) {
+		measures.insert(self.id, self);
+		self.child.collect_measures(measures);
+	}
+
+	fn draw<'a, B: Backend>(&self, state: &State, view: &mut B::View<'a>, font_state: &mut FontState, layer: i32) {
+		// nothing to draw within this subtree
+		if !self.layers.contains(&layer) {
+			return;
+		}
 
-		view.apply_bounds(self.layout.get_padding(state).unwrap());
-		self.child.draw::<B>(state, &self.layout, view, font_state);
+		state.scoped(self.id, || {
+			if self.layer == layer {
+				view.push();
+				view.set_viewport(self.boxes.padding_box);
+				state.track(self.id, Phase::Draw, || self.component.draw::<B>(state, view, font_state));
+				view.restore();
+			}
+
+			// descendants in higher layers are not cut off
+			let clips = self.overflow != Overflow::Visible && self.layer == layer;
+
+			if clips {
+				view.push();
+				view.set_clip(self.boxes.padding_box);
+			}
+
+			self.child.draw::<B>(state, view, font_state, layer);
+
+			if clips {
+				view.restore();
+			}
+		})
+	}
+
+	fn hit_test(&mut self, pos: (f32, f32), tree_idx: usize, clipped: &mut Layers, path: &mut Vec<EventHandlerRef>) {
+		let inside = self.boxes.padding_box.contains(pos);
+
+		if inside && !clipped.contains(&self.layer) {
+			path.push(EventHandlerRef::new(self, tree_idx));
+		}
+
+		// only the descendants within the same layer are cut off
+		let clips = !inside && self.overflow != Overflow::Visible && !clipped.contains(&self.layer);
+
+		if clips {
+			add_layer(clipped, self.layer);
+		}
+
+		// positioned descendants may lie outside of their parent
+		if (inside || self.positioned) && self.layers.iter().any(|l| !clipped.contains(l)) {
+			self.child.hit_test(pos, tree_idx, clipped, path);
+		}
+
+		if clips {
+			clipped.retain(|l| *l != self.layer);
+		}
+	}
+
+	fn update(&mut self, state: &mut State, dirty: &HashMap<ContainerId, Dirty>) {
+		if dirty.get(&self.id) == Some(&Dirty::Rebuild) {
+			self.rebuild(state, dirty);
+		} else {
+			state.scoped_mut(self.id, |state| self.child.update(state, dirty));
+		}
+	}
+
+	fn unmount(&mut self, state: &mut State) {
+		state.unmount(self.id);
+		self.child.unmount(state);
+	}
+}
+
+impl<T: Component> Measure for ComponentContainer<T> {
+	fn measure(&self, state: &State, available_size: (Option<u32>, Option<u32>), font_state: &mut FontState) -> (u32, u32) {
+		// the size depends on the values read while measuring, just like on the layout
+		state.scoped(self.id, || state.track_resumed(self.id, Phase::Layout, || {
+			self.component.measure(state, available_size, font_state)
+		}))
+	}
+}
+
+impl<T: Component> EventHandler for ComponentContainer<T> {
+	fn handle(&mut self, ev: &InputEvent, state: &mut State) -> bool {
+		state.with_owner(self.id, |state| self.component.on_event(ev, state))
+	}
+
+	fn boxes(&self) -> LayoutBoxes {
+		self.boxes
+	}
+
+	fn id(&self) -> ContainerId {
+		self.id
+	}
+
+	fn layer(&self) -> i32 {
+		self.layer
 	}
 }
 
 impl ContainerLike for () {
-	fn draw<'a, B: Backend>(&self, _state: &State, _parent_layout: &Layout, _view: &mut B::View<'a>, font_state: &mut FontState) {}
+	fn layout_nodes(&mut self, _state: &State, _engine: &mut LayoutEngine, _nodes: &mut Vec<taffy::NodeId>) -> bool {
+		false
+	}
+
+	fn apply_layout(&mut self, _engine: &mut LayoutEngine, _origin: (i32, i32), _layer: i32, _layers: &mut Layers) {}
+
+	fn collect_measures<'a>(&'a self, _measures: &mut HashMap<ContainerId, &'a dyn Measure>) {}
+
+	fn draw<'a, B: Backend>(&self, _state: &State, _view: &mut B::View<'a>, _font_state: &mut FontState, _layer: i32) {}
+
+	fn hit_test(&mut self, _pos: (f32, f32), _tree_idx: usize, _clipped: &mut Layers, _path: &mut Vec<EventHandlerRef>) {}
+
+	fn update(&mut self, _state: &mut State, _dirty: &HashMap<ContainerId, Dirty>) {}
+
+	fn unmount(&mut self, _state: &mut State) {}
+}
+
+impl<T: Component> ContainerLike for Vec<Box<ComponentContainer<T>>> {
+	fn layout_nodes(&mut self, state: &State, engine: &mut LayoutEngine, nodes: &mut Vec<taffy::NodeId>) -> bool {
+		let mut positioned = false;
+
+		for c in self {
+			positioned |= c.layout_nodes(state, engine, nodes);
+		}
+
+		positioned
+	}
+
+	fn apply_layout(&mut self, engine: &mut LayoutEngine, origin: (i32, i32), layer: i32, layers: &mut Layers) {
+		for c in self {
+			c.apply_layout(engine, origin, layer, layers);
+		}
+	}
+
+	fn collect_measures<'a>(&'a self, measures: &mut HashMap<ContainerId, &'a dyn Measure>) {
+		for c in self {
+			c.collect_measures(measures);
+		}
+	}
+
+	fn draw<'a, B: Backend>(&self, state: &State, view: &mut B::View<'a>, font_state: &mut FontState, layer: i32) {
+		for c in self {
+			c.draw::<B>(state, view, font_state, layer);
+		}
+	}
+
+	fn hit_test(&mut self, pos: (f32, f32), tree_idx: usize, clipped: &mut Layers, path: &mut Vec<EventHandlerRef>) {
+		for c in self {
+			c.hit_test(pos, tree_idx, clipped, path);
+		}
+	}
+
+	fn update(&mut self, state: &mut State, dirty: &HashMap<ContainerId, Dirty>) {
+		for c in self {
+			c.update(state, dirty);
+		}
+	}
+
+	fn unmount(&mut self, state: &mut State) {
+		for c in self {
+			c.unmount(state);
+		}
+	}
+}
+
+impl<T: ContainerLike> ContainerLike for Option<T> {
+	fn layout_nodes(&mut self, state: &State, engine: &mut LayoutEngine, nodes: &mut Vec<taffy::NodeId>) -> bool {
+		match self {
+			Some(c) => c.layout_nodes(state, engine, nodes),
+			None => false,
+		}
+	}
+
+	fn apply_layout(&mut self, engine: &mut LayoutEngine, origin: (i32, i32), layer: i32, layers: &mut Layers) {
+		if let Some(c) = self {
+			c.apply_layout(engine, origin, layer, layers);
+		}
+	}
+
+	fn collect_measures<'a>(&'a self, measures: &mut HashMap<ContainerId, &'a dyn Measure>) {
+		if let Some(c) = self {
+			c.collect_measures(measures);
+		}
+	}
+
+	fn draw<'a, B: Backend>(&self, state: &State, view: &mut B::View<'a>, font_state: &mut FontState, layer: i32) {
+		if let Some(c) = self {
+			c.draw::<B>(state, view, font_state, layer);
+		}
+	}
+
+	fn hit_test(&mut self, pos: (f32, f32), tree_idx: usize, clipped: &mut Layers, path: &mut Vec<EventHandlerRef>) {
+		if let Some(c) = self {
+			c.hit_test(pos, tree_idx, clipped, path);
+		}
+	}
+
+	fn update(&mut self, state: &mut State, dirty: &HashMap<ContainerId, Dirty>) {
+		if let Some(c) = self {
+			c.update(state, dirty);
+		}
+	}
+
+	fn unmount(&mut self, state: &mut State) {
+		if let Some(c) = self {
+			c.unmount(state);
+		}
+	}
+}
+
+impl<A: ContainerLike, B: ContainerLike> ContainerLike for Either<A, B> {
+	fn layout_nodes(&mut self, state: &State, engine: &mut LayoutEngine, nodes: &mut Vec<taffy::NodeId>) -> bool {
+		match self {
+			Either::Left(c) => c.layout_nodes(state, engine, nodes),
+			Either::Right(c) => c.layout_nodes(state, engine, nodes),
+		}
+	}
+
+	fn apply_layout(&mut self, engine: &mut LayoutEngine, origin: (i32, i32), layer: i32, layers: &mut Layers) {
+		match self {
+			Either::Left(c) => c.apply_layout(engine, origin, layer, layers),
+			Either::Right(c) => c.apply_layout(engine, origin, layer, layers),
+		}
+	}
+
+	fn collect_measures<'a>(&'a self, measures: &mut HashMap<ContainerId, &'a dyn Measure>) {
+		match self {
+			Either::Left(c) => c.collect_measures(measures),
+			Either::Right(c) => c.collect_measures(measures),
+		}
+	}
+
+	fn draw<'a, Ba: Backend>(&self, state: &State, view: &mut Ba::View<'a>, font_state: &mut FontState, layer: i32) {
+		match self {
+			Either::Left(c) => c.draw::<Ba>(state, view, font_state, layer),
+			Either::Right(c) => c.draw::<Ba>(state, view, font_state, layer),
+		}
+	}
+
+	fn hit_test(&mut self, pos: (f32, f32), tree_idx: usize, clipped: &mut Layers, path: &mut Vec<EventHandlerRef>) {
+		match self {
+			Either::Left(c) => c.hit_test(pos, tree_idx, clipped, path),
+			Either::Right(c) => c.hit_test(pos, tree_idx, clipped, path),
+		}
+	}
+
+	fn update(&mut self, state: &mut State, dirty: &HashMap<ContainerId, Dirty>) {
+		match self {
+			Either::Left(c) => c.update(state, dirty),
+			Either::Right(c) => c.update(state, dirty),
+		}
+	}
+
+	fn unmount(&mut self, state: &mut State) {
+		match self {
+			Either::Left(c) => c.unmount(state),
+			Either::Right(c) => c.unmount(state),
+		}
+	}
 }
 
 
@@ -47,6 +477,9 @@ pub trait Containable {
 	type Container: ContainerLike;
 
 	fn contain(self, state: &mut State) -> Self::Container;
+
+	/// Update an existing container to match `self`, keeping the containers that did not change.
+	fn reconcile(self, container: &mut Self::Container, state: &mut State, dirty: &HashMap<ContainerId, Dirty>);
 }
 
 impl<T: Component> Containable for T {
@@ -55,6 +488,21 @@ impl<T: Component> Containable for T {
 	fn contain(self, state: &mut State) -> Self::Container {
 		ComponentContainer::new(self, state)
 	}
+
+	fn reconcile(self, container: &mut Self::Container, state: &mut State, dirty: &HashMap<ContainerId, Dirty>) {
+		if self.should_rebuild(&container.component) {
+			container.component = self;
+
+			// running transitions are carried over instead of starting from scratch
+			let layout = state.scoped_mut(container.id, |state| container.component.layout(state));
+			layout.continue_animations(&container.layout);
+			container.layout = layout;
+
+			container.rebuild(state, dirty);
+		} else {
+			container.update(state, dirty);
+		}
+	}
 }
 
 impl Containable for () {
@@ -63,14 +511,102 @@ impl Containable for () {
 	fn contain(self, _: &mut State) -> Self::Container {
 		self
 	}
+
+	fn reconcile(self, _container: &mut Self::Container, _state: &mut State, _dirty: &HashMap<ContainerId, Dirty>) {}
+}
+
+impl<T: Component> Containable for Vec<T> {
+	// boxed, so that the containers keep their address when the list changes
+	type Container = Vec<Box<ComponentContainer<T>>>;
+
+	fn contain(self, state: &mut State) -> Self::Container {
+		self.into_iter()
+			.map(|c| Box::new(ComponentContainer::new(c, state)))
+			.collect()
+	}
+
+	fn reconcile(self, container: &mut Self::Container, state: &mut State, dirty: &HashMap<ContainerId, Dirty>) {
+		let mut old: Vec<Option<Box<ComponentContainer<T>>>> = container.drain(..).map(Some).collect();
+
+		let keyed: HashMap<u64, usize> = old.iter()
+			.enumerate()
+			.filter_map(|(i, c)| Some((c.as_ref()?.component.key()?, i)))
+			.collect();
+
+		for (i, new) in self.into_iter().enumerate() {
+			let reused = match new.key() {
+				Some(key) => keyed.get(&key).and_then(|i| old[*i].take()),
+				None => match old.get_mut(i) {
+					Some(c) if c.as_ref().is_some_and(|c| c.component.key().is_none()) => c.take(),
+					_ => None,
+				},
+			};
+
+			container.push(match reused {
+				Some(mut c) => {
+					new.reconcile(&mut c, state, dirty);
+					c
+				},
+				None => Box::new(ComponentContainer::new(new, state)),
+			});
+		}
+
+		for mut c in old.into_iter().flatten() {
+			c.unmount(state);
+		}
+	}
+}
+
+impl<T: Component> Containable for Option<T> {
+	type Container = Option<ComponentContainer<T>>;
+
+	fn contain(self, state: &mut State) -> Self::Container {
+		self.map(|c| ComponentContainer::new(c, state))
+	}
+
+	fn reconcile(self, container: &mut Self::Container, state: &mut State, dirty: &HashMap<ContainerId, Dirty>) {
+		match (self, container.as_mut()) {
+			(Some(new), Some(c)) if new.key() == c.component.key() => new.reconcile(c, state, dirty),
+			(new, _) => {
+				container.unmount(state);
+				*container = new.contain(state);
+			}
+		}
+	}
+}
+
+impl<A: Component, B: Component> Containable for Either<A, B> {
+	type Container = Either<ComponentContainer<A>, ComponentContainer<B>>;
+
+	fn contain(self, state: &mut State) -> Self::Container {
+		match self {
+			Either::Left(c) => Either::Left(ComponentContainer::new(c, state)),
+			Either::Right(c) => Either::Right(ComponentContainer::new(c, state)),
+		}
+	}
+
+	fn reconcile(self, container: &mut Self::Container, state: &mut State, dirty: &HashMap<ContainerId, Dirty>) {
+		match (self, container) {
+			(Either::Left(new), Either::Left(c)) => new.reconcile(c, state, dirty),
+			(Either::Right(new), Either::Right(c)) => new.reconcile(c, state, dirty),
+			(new, container) => {
+				container.unmount(state);
+				*container = new.contain(state);
+			}
+		}
+	}
 }
 
 
 /// A tree of [Container]s.
 pub struct ComponentTree<T: Component> {
-	tree: ComponentContainer<T>,
+	// boxed, so that the containers keep their address when the tree is moved
+	tree: Box<ComponentContainer<T>>,
 	_pin: std::marker::PhantomPinned,
 	tree_idx: usize,
+	focus: Option<EventHandlerRef>,
+	/// Reused by every layout pass.
+	engine: LayoutEngine,
 }
 
 impl<T: Component> ComponentTree<T> {
@@ -78,9 +614,11 @@ impl<T: Component> ComponentTree<T> {
 		static TREE_IDX: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
 
 		let tree = Self {
-			tree: ComponentContainer::new(root, state),
+			tree: Box::new(ComponentContainer::new(root, state)),
 			_pin: std::marker::PhantomPinned,
-			tree_idx: TREE_IDX.load(std::sync::atomic::Ordering::Relaxed)
+			tree_idx: TREE_IDX.load(std::sync::atomic::Ordering::Relaxed),
+			focus: None,
+			engine: LayoutEngine::default(),
 		};
 
 		TREE_IDX.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
@@ -98,95 +636,214 @@ impl<T: Component> ComponentTree<T> {
 		Some(unsafe { handler.container.as_mut() })
 	}
 
+	/// Rebuild all containers that are marked as [Dirty::Rebuild].
+	pub fn update(&mut self, state: &mut State, dirty: &HashMap<ContainerId, Dirty>) {
+		self.tree.update(state, dirty);
+
+		// the focused container might have been removed
+		let unmounted = state.take_unmounted();
+
+		if self.focus.is_some_and(|f| unmounted.contains(&f.id)) {
+			self.focus = None;
+		}
+	}
+
+	/// Compute the geometry of all containers for the given window size.
+	pub fn layout(&mut self, state: &mut State, window_size: (u32, u32), font_state: &mut FontState) {
+		self.engine.clear();
+
+		let mut nodes = Vec::new();
+		self.tree.layout_nodes(state, &mut self.engine, &mut nodes);
+
+		let mut measures = HashMap::new();
+		self.tree.collect_measures(&mut measures);
+
+		self.engine.compute(&nodes, window_size, state, font_state, &measures);
+		self.tree.apply_layout(&mut self.engine, (0, 0), 0, &mut Layers::new());
+
+		// the scrollbars depend on the extents
+		state.set(state.scroll_extents, self.engine.take_scroll_extents());
+	}
+
+	/// Draw all containers. Call [ComponentTree::layout] beforehand.
 	pub fn draw<'a, B: Backend>(&self, state: &State, view: &mut B::View<'a>, font_state: &mut FontState) {
-		self.tree.draw::<B>(state, &Layout::new(), view, font_state);
+		// overlays are drawn on top of everything below them,
+		// each pass only visits the subtrees that contain the layer
+		for layer in &self.tree.layers {
+			self.tree.draw::<B>(state, view, font_state, *layer);
+		}
+	}
+
+	/// Get the path of containers under `pos` (outermost and lowest layer first), as of the last layout pass.
+	pub fn hit_test(&mut self, pos: (f32, f32)) -> Vec<EventHandlerRef> {
+		let mut path = Vec::new();
+		self.tree.hit_test(pos, self.tree_idx, &mut Layers::new(), &mut path);
+
+		// overlays receive events before the layers below them
+		path.sort_by_key(|h| h.layer);
+		path
+	}
+
+	/// Pass an [InputEvent] to the components it concerns.
+	/// 
+	/// Events with a position bubble up from the innermost container under that position,
+	/// all others are passed to the focused container (or the root, if nothing has focus).
+	/// Releases outside of the focused container are passed to it as well, so it can end a drag.
+	/// Returns `true` if a component handled the event.
+	pub fn dispatch(&mut self, ev: &InputEvent, state: &mut State) -> bool {
+		let path = match ev.pos() {
+			Some(pos) => self.hit_test(pos),
+			None => vec![self.focus.unwrap_or(EventHandlerRef::new(&mut *self.tree, self.tree_idx))],
+		};
+
+		let released_outside = match ev {
+			InputEvent::PointerReleased { .. } |
+			InputEvent::Touch { phase: TouchPhase::Ended | TouchPhase::Cancelled, .. } => {
+				self.focus.filter(|f| !path.iter().any(|h| h.id == f.id))
+			},
+			_ => None,
+		};
+
+		if let Some(focus) = released_outside {
+			if let Some(h) = self.get_event_handler(focus) {
+				h.handle(ev, state);
+			}
+		}
+
+		let mut handled_by = None;
+
+		for handler in path.into_iter().rev() {
+			if self.get_event_handler(handler).is_some_and(|h| h.handle(ev, state)) {
+				handled_by = Some(handler);
+				break;
+			}
+		}
+
+		if let InputEvent::PointerPressed { .. } = ev {
+			self.focus = handled_by;
+		}
+
+		handled_by.is_some()
 	}
 }
 
+/// Something that can receive [InputEvent]s.
 pub trait EventHandler {
-	fn handle(&mut self, ev: Event);
+	fn handle(&mut self, ev: &InputEvent, state: &mut State) -> bool;
+
+	/// Get the geometry of the handler, as computed by the last layout pass.
+	fn boxes(&self) -> LayoutBoxes;
+
+	fn id(&self) -> ContainerId;
+
+	/// Get the layer the handler is drawn in, see [Layout::set_z_index].
+	fn layer(&self) -> i32;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A reference to an [EventHandler] within a [ComponentTree].
+/// 
+/// Use [ComponentTree::get_event_handler] to access it.
+#[derive(Debug, Clone, Copy)]
 pub struct EventHandlerRef {
 	container: std::ptr::NonNull<dyn EventHandler>,
 	tree_idx: usize,
+	id: ContainerId,
+	layer: i32,
 }
 
+impl EventHandlerRef {
+	fn new(container: &mut (dyn EventHandler + 'static), tree_idx: usize) -> Self {
+		Self {
+			id: container.id(),
+			layer: container.layer(),
+			container: std::ptr::NonNull::from(container),
+			tree_idx,
+		}
+	}
+}
+
+impl PartialEq for EventHandlerRef {
+	fn eq(&self, other: &Self) -> bool {
+		self.tree_idx == other.tree_idx && std::ptr::addr_eq(self.container.as_ptr(), other.container.as_ptr())
+	}
+}
+
+impl Eq for EventHandlerRef {}
+
 
 macro_rules! impl_tuple_container {
 	($($name:ident),*) => {
-		impl< $($name: Component),* > container::ContainerLike for ($(container::ComponentContainer< $name >,)*) {
-			fn draw<'a, Ba: Backend>(&self, state: &State, parent_layout: &Layout, view: &mut Ba::View<'a>, font_state: &mut FontState) {
-				#![allow(unused_assignments)]
-
+		impl< $($name: container::ContainerLike),* > container::ContainerLike for ($($name,)*) {
+			fn layout_nodes(&mut self, state: &State, engine: &mut layout::LayoutEngine, nodes: &mut Vec<taffy::NodeId>) -> bool {
 				#[allow(non_snake_case)]
 				let ($($name,)*) = self;
 
-				let mut count = 0;
-				let mut spacers = 0;
+				// not short-circuiting, all of them have to be laid out
+				false $( | $name.layout_nodes(state, engine, nodes) )*
+			}
 
-				let mut counts = smallvec::SmallVec::<[u32; 16]>::new();
+			fn apply_layout(&mut self, engine: &mut layout::LayoutEngine, origin: (i32, i32), layer: i32, layers: &mut container::Layers) {
+				#[allow(non_snake_case)]
+				let ($($name,)*) = self;
 
-				$(
-					{
-						let c = $name.layout.get_flex(state).unwrap();
-						counts.push(c);
-						count += c;
-						spacers += 1;
-					}
-				)*
+				$( $name.apply_layout(engine, origin, layer, layers); )*
+			}
 
-				spacers -= 1;
+			fn collect_measures<'a>(&'a self, measures: &mut HashMap<ContainerId, &'a dyn layout::Measure>) {
+				#[allow(non_snake_case)]
+				let ($($name,)*) = self;
 
-				let orient = parent_layout.get_stack_orientation(state).unwrap();
-				let size = view.viewport_size();
-				let size = match orient {
-					Orientation::Horizontal => size.0,
-					Orientation::Vertical => size.1,
-				};
+				$( $name.collect_measures(measures); )*
+			}
 
-				let spacing = parent_layout.get_stack_spacing(state).unwrap();
+			fn draw<'a, Ba: Backend>(&self, state: &State, view: &mut Ba::View<'a>, font_state: &mut FontState, layer: i32) {
+				#[allow(non_snake_case)]
+				let ($($name,)*) = self;
 
-				let mut offset = 0;
+				$( $name.draw::<Ba>(state, view, font_state, layer); )*
+			}
 
-				let component_space = (size - spacers * spacing);
-				let size_per_count = (component_space as f32 / count as f32);
+			fn hit_test(&mut self, pos: (f32, f32), tree_idx: usize, clipped: &mut Layers, path: &mut Vec<EventHandlerRef>) {
+				#[allow(non_snake_case)]
+				let ($($name,)*) = self;
 
-				let mut cur_c = 0;
+				$( $name.hit_test(pos, tree_idx, clipped, path); )*
+			}
 
-				$(
-					{
-						let c = counts[cur_c];
-						let mut el_size = (size_per_count * c as f32) as u32;
+			fn update(&mut self, state: &mut State, dirty: &HashMap<ContainerId, Dirty>) {
+				#[allow(non_snake_case)]
+				let ($($name,)*) = self;
 
-						if cur_c == counts.len()-1 {
-							el_size = size - offset;
-						}
+				$( $name.update(state, dirty); )*
+			}
 
-						view.push();
-						match orient {
-							Orientation::Horizontal => view.set_viewport_horizontal(offset, el_size),
-							Orientation::Vertical => view.set_viewport_vertical(offset, el_size),
-						}
-						$name.draw::<Ba>(state, parent_layout, view, font_state);
-						view.restore();
+			fn unmount(&mut self, state: &mut State) {
+				#[allow(non_snake_case)]
+				let ($($name,)*) = self;
 
-						cur_c += 1;
-						offset += el_size + spacing;
-					}
-				)*
+				$( $name.unmount(state); )*
 			}
 		}
 
-		impl< $($name: Component),* > container::Containable for ($($name,)*) {
-			type Container = ($(container::ComponentContainer< $name >,)*);
+		impl< $($name: container::Containable),* > container::Containable for ($($name,)*) {
+			type Container = ($(< $name as container::Containable>::Container,)*);
 
 			fn contain(self, state: &mut State) -> Self::Container {
 				#[allow(non_snake_case)]
 				let ($($name,)*) = self;
 
-				($(container::ComponentContainer::new($name, state),)*)
+				($($name.contain(state),)*)
+			}
+
+			fn reconcile(self, container: &mut Self::Container, state: &mut State, dirty: &HashMap<ContainerId, Dirty>) {
+				#[allow(non_snake_case)]
+				let ($($name,)*) = self;
+
+				paste::paste! {
+					#[allow(non_snake_case)]
+					let ($( [<c_ $name>], )*) = container;
+					$( $name.reconcile([<c_ $name>], state, dirty); )*
+				}
 			}
 		}
 	};
@@ -194,3 +851,410 @@ macro_rules! impl_tuple_container {
 
 impl_tuple!(impl_tuple_container);
 
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Counts the events it receives and handles them if `handles` is set.
+	struct Counter {
+		count: arena::Ref<u32>,
+		handles: bool,
+	}
+
+	impl Component for Counter {
+		type Child = ();
+
+		fn build(&self, _: &mut State) -> Self::Child {}
+
+		fn on_event(&self, _: &InputEvent, state: &mut State) -> bool {
+			state.mutate_ref(self.count, |c| *c += 1);
+			self.handles
+		}
+	}
+
+	struct Root {
+		outer: arena::Ref<u32>,
+		left: arena::Ref<u32>,
+		right: arena::Ref<u32>,
+	}
+
+	impl Component for Root {
+		type Child = (Counter, Counter);
+
+		fn build(&self, _: &mut State) -> Self::Child {
+			(Counter { count: self.left, handles: true }, Counter { count: self.right, handles: false })
+		}
+
+		fn on_event(&self, _: &InputEvent, state: &mut State) -> bool {
+			state.mutate_ref(self.outer, |c| *c += 1);
+			true
+		}
+	}
+
+	#[test]
+	fn test_dispatch() {
+		let mut state = State::new();
+		let (outer, left, right) = (state.alloc(0), state.alloc(0), state.alloc(0));
+
+		let mut tree = ComponentTree::new(Root { outer, left, right }, &mut state);
+
+		// lay out once to know where the components are
+		tree.layout(&mut state, (20, 10), &mut FontState::new());
+
+		let count = |state: &State| (*state.get(outer).unwrap(), *state.get(left).unwrap(), *state.get(right).unwrap());
+
+		// left handles the event itself
+		assert!(tree.dispatch(&InputEvent::PointerPressed { pos: (2.0, 5.0), button: MouseButton::Left }, &mut state));
+		assert_eq!(count(&state), (0, 1, 0));
+
+		// keyboard input goes to the focused component
+		tree.dispatch(&InputEvent::Text('a'), &mut state);
+		assert_eq!(count(&state), (0, 2, 0));
+
+		// right does not handle it, so it bubbles up
+		assert!(tree.dispatch(&InputEvent::PointerPressed { pos: (15.0, 5.0), button: MouseButton::Left }, &mut state));
+		assert_eq!(count(&state), (1, 2, 1));
+
+		// outside of the window
+		assert!(!tree.dispatch(&InputEvent::PointerMoved { pos: (25.0, 5.0) }, &mut state));
+		assert_eq!(count(&state), (1, 2, 1));
+
+		// focus moved to the root
+		tree.dispatch(&InputEvent::Text('a'), &mut state);
+		assert_eq!(count(&state), (2, 2, 1));
+	}
+
+	#[test]
+	fn test_hit_test() {
+		let mut state = State::new();
+		let (outer, left, right) = (state.alloc(0), state.alloc(0), state.alloc(0));
+
+		let mut tree = ComponentTree::new(Root { outer, left, right }, &mut state);
+		tree.layout(&mut state, (20, 10), &mut FontState::new());
+
+		let boxes = |tree: &mut ComponentTree<Root>, pos| tree.hit_test(pos)
+			.into_iter()
+			.map(|h| tree.get_event_handler(h).unwrap().boxes().padding_box)
+			.collect::<Vec<_>>();
+
+		assert_eq!(boxes(&mut tree, (2.0, 5.0)), [Rect::new((0, 0), (20, 10)), Rect::new((0, 0), (10, 10))]);
+		assert_eq!(boxes(&mut tree, (10.0, 0.0)), [Rect::new((0, 0), (20, 10)), Rect::new((10, 0), (10, 10))]);
+		assert!(boxes(&mut tree, (20.0, 5.0)).is_empty());
+	}
+
+	/// Floats above its siblings.
+	struct Popup {
+		count: arena::Ref<u32>,
+	}
+
+	impl Component for Popup {
+		type Child = ();
+
+		fn build(&self, _: &mut State) -> Self::Child {}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let mut layout = Layout::new();
+			layout.set_position(Position::Absolute);
+			layout.set_left(Some(5));
+			layout.set_top(Some(2));
+			layout.set_width(Length::Px(10));
+			layout.set_height(Length::Px(4));
+			layout.set_z_index(Some(1));
+			layout
+		}
+
+		fn on_event(&self, _: &InputEvent, state: &mut State) -> bool {
+			state.mutate_ref(self.count, |c| *c += 1);
+			true
+		}
+	}
+
+	struct Menu {
+		popup: arena::Ref<u32>,
+		content: arena::Ref<u32>,
+	}
+
+	impl Component for Menu {
+		type Child = (Popup, Counter);
+
+		fn build(&self, _: &mut State) -> Self::Child {
+			(Popup { count: self.popup }, Counter { count: self.content, handles: true })
+		}
+	}
+
+	#[test]
+	fn test_overlay() {
+		let mut state = State::new();
+		let (popup, content) = (state.alloc(0), state.alloc(0));
+
+		let mut tree = ComponentTree::new(Menu { popup, content }, &mut state);
+		tree.layout(&mut state, (20, 10), &mut FontState::new());
+
+		let click = |pos| InputEvent::PointerPressed { pos, button: MouseButton::Left };
+
+		// the popup comes first, but its layer is above the content
+		tree.dispatch(&click((6.0, 3.0)), &mut state);
+		assert_eq!((*state.get(popup).unwrap(), *state.get(content).unwrap()), (1, 0));
+
+		let path = tree.hit_test((6.0, 3.0));
+		assert_eq!(path.iter().map(|h| h.layer).collect::<Vec<_>>(), [0, 0, 1]);
+
+		tree.dispatch(&click((2.0, 3.0)), &mut state);
+		assert_eq!((*state.get(popup).unwrap(), *state.get(content).unwrap()), (1, 1));
+
+		// drawing the popup's layer skips the content, hit testing outside of the content skips its children
+		assert_eq!(tree.tree.layers.as_slice(), [0, 1]);
+		assert_eq!(tree.tree.child.0.layers.as_slice(), [1]);
+		assert_eq!(tree.tree.child.1.layers.as_slice(), [0]);
+		assert!(tree.tree.positioned);
+		assert!(!tree.tree.child.1.positioned);
+	}
+
+	struct Indicator {
+		loading: bool,
+		builds: arena::Ref<u32>,
+	}
+
+	impl Component for Indicator {
+		type Child = ();
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			state.mutate_ref(self.builds, |b| *b += 1);
+		}
+
+		fn should_rebuild(&self, old: &Self) -> bool {
+			self.loading != old.loading
+		}
+	}
+
+	struct Status {
+		loading: arena::Ref<bool>,
+		builds: arena::Ref<u32>,
+	}
+
+	impl Component for Status {
+		type Child = (Indicator, Indicator);
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			let loading = *state.get(self.loading).unwrap();
+
+			(Indicator { loading, builds: self.builds }, Indicator { loading: false, builds: self.builds })
+		}
+	}
+
+	#[test]
+	fn test_rebuild() {
+		let mut state = State::new();
+		let (loading, builds) = (state.alloc(true), state.alloc(0));
+
+		let mut tree = ComponentTree::new(Status { loading, builds }, &mut state);
+		let ids = (tree.tree.child.0.id(), tree.tree.child.1.id());
+
+		assert_eq!(state.get(builds), Some(&2));
+
+		state.set(loading, false);
+
+		let dirty = state.take_dirty();
+		assert_eq!(dirty.get(&tree.tree.id()), Some(&Dirty::Rebuild));
+
+		tree.update(&mut state, &dirty);
+
+		// only the indicator whose input changed has been rebuilt
+		assert_eq!(state.get(builds), Some(&3));
+		assert!(!tree.tree.child.0.component.loading);
+
+		// the existing containers have been kept
+		assert_eq!((tree.tree.child.0.id(), tree.tree.child.1.id()), ids);
+		assert!(state.take_dirty().is_empty());
+	}
+
+	struct Row(u64);
+
+	impl Component for Row {
+		type Child = ();
+
+		fn build(&self, _: &mut State) -> Self::Child {}
+
+		fn on_event(&self, _: &InputEvent, _: &mut State) -> bool {
+			true
+		}
+
+		fn key(&self) -> Option<u64> {
+			Some(self.0)
+		}
+	}
+
+	struct List {
+		len: arena::Ref<u64>,
+		reversed: arena::Ref<bool>,
+	}
+
+	impl Component for List {
+		type Child = Vec<Row>;
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			let rows = (0..*state.get(self.len).unwrap()).map(Row);
+
+			match *state.get(self.reversed).unwrap() {
+				true => rows.rev().collect(),
+				false => rows.collect(),
+			}
+		}
+	}
+
+	#[test]
+	fn test_keyed_list() {
+		let mut state = State::new();
+		let (len, reversed) = (state.alloc(3), state.alloc(false));
+
+		let mut tree = ComponentTree::new(List { len, reversed }, &mut state);
+		tree.layout(&mut state, (30, 10), &mut FontState::new());
+
+		let rows = |tree: &ComponentTree<List>| tree.tree.child.iter()
+			.map(|c| (c.component.0, c.id(), c.boxes().margin_box.x))
+			.collect::<Vec<_>>();
+
+		let before = rows(&tree);
+		assert_eq!(before.iter().map(|r| (r.0, r.2)).collect::<Vec<_>>(), [(0, 0), (1, 10), (2, 20)]);
+
+		// focus the last row
+		assert!(tree.dispatch(&InputEvent::PointerPressed { pos: (25.0, 5.0), button: MouseButton::Left }, &mut state));
+		assert!(tree.focus.is_some());
+
+		state.set(reversed, true);
+		let dirty = state.take_dirty();
+		tree.update(&mut state, &dirty);
+		tree.layout(&mut state, (30, 10), &mut FontState::new());
+
+		// the containers moved along with their keys
+		let after = rows(&tree);
+		assert_eq!(after.iter().map(|r| (r.0, r.2)).collect::<Vec<_>>(), [(2, 0), (1, 10), (0, 20)]);
+		assert_eq!(after[0].1, before[2].1);
+		assert_eq!(after[2].1, before[0].1);
+		assert!(tree.focus.is_some());
+
+		// removing the focused row clears the focus
+		state.set(len, 2);
+		let dirty = state.take_dirty();
+		tree.update(&mut state, &dirty);
+
+		assert_eq!(rows(&tree).iter().map(|r| r.1).collect::<Vec<_>>(), [before[1].1, before[0].1]);
+		assert!(tree.focus.is_none());
+	}
+
+	struct Conditional(arena::Ref<bool>);
+
+	impl Component for Conditional {
+		type Child = (Maybe, Switch);
+
+		fn build(&self, _: &mut State) -> Self::Child {
+			(Maybe(self.0), Switch(self.0))
+		}
+	}
+
+	struct Maybe(arena::Ref<bool>);
+
+	impl Component for Maybe {
+		type Child = Option<Row>;
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			state.get(self.0).unwrap().then_some(Row(0))
+		}
+	}
+
+	struct Switch(arena::Ref<bool>);
+
+	impl Component for Switch {
+		type Child = Either<Row, Empty>;
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			match *state.get(self.0).unwrap() {
+				true => Either::Left(Row(0)),
+				false => Either::Right(Empty),
+			}
+		}
+	}
+
+	struct Empty;
+
+	impl Component for Empty {
+		type Child = ();
+
+		fn build(&self, _: &mut State) -> Self::Child {}
+	}
+
+	#[test]
+	fn test_conditional_children() {
+		let mut state = State::new();
+		let show = state.alloc(false);
+
+		let mut tree = ComponentTree::new(Conditional(show), &mut state);
+		tree.layout(&mut state, (20, 10), &mut FontState::new());
+
+		assert_eq!(tree.hit_test((5.0, 5.0)).len(), 2);
+		assert_eq!(tree.hit_test((15.0, 5.0)).len(), 3);
+
+		state.set(show, true);
+		let dirty = state.take_dirty();
+		tree.update(&mut state, &dirty);
+		tree.layout(&mut state, (20, 10), &mut FontState::new());
+
+		let (maybe, switch) = &tree.tree.child;
+		assert_eq!(maybe.child.as_ref().unwrap().boxes().margin_box, Rect::new((0, 0), (10, 10)));
+		assert!(matches!(switch.child, Either::Left(_)));
+
+		assert_eq!(tree.hit_test((5.0, 5.0)).len(), 3);
+		assert_eq!(tree.hit_test((15.0, 5.0)).len(), 3);
+
+		state.set(show, false);
+		let dirty = state.take_dirty();
+		tree.update(&mut state, &dirty);
+
+		let (maybe, switch) = &tree.tree.child;
+		assert!(maybe.child.is_none());
+		assert!(matches!(switch.child, Either::Right(_)));
+	}
+
+	/// Mixes a component, an optional one and a list within one tuple.
+	struct Mixed(arena::Ref<bool>);
+
+	impl Component for Mixed {
+		type Child = (Empty, Option<Empty>, Vec<Empty>);
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			match *state.get(self.0).unwrap() {
+				true => (Empty, Some(Empty), vec![Empty, Empty]),
+				false => (Empty, None, Vec::new()),
+			}
+		}
+	}
+
+	#[test]
+	fn test_mixed_tuple() {
+		let mut state = State::new();
+		let show = state.alloc(false);
+
+		let mut tree = ComponentTree::new(Mixed(show), &mut state);
+		assert!(tree.tree.child.1.is_none() && tree.tree.child.2.is_empty());
+
+		state.set(show, true);
+		let dirty = state.take_dirty();
+		tree.update(&mut state, &dirty);
+
+		let (first, maybe, list) = &tree.tree.child;
+		assert!(maybe.is_some());
+		assert_eq!(list.len(), 2);
+
+		let first = first.id;
+
+		state.set(show, false);
+		let dirty = state.take_dirty();
+		tree.update(&mut state, &dirty);
+
+		// the first container is kept
+		assert!(tree.tree.child.1.is_none() && tree.tree.child.2.is_empty());
+		assert_eq!(tree.tree.child.0.id, first);
+	}
+}
@@ -0,0 +1,190 @@
+//! Sharing values with descendants.
+//!
+//! A component can [State::provide] a value while it is built, which its descendants can look up by type
+//! using [State::context] while they are built, laid out or drawn. Values provided by nested components shadow
+//! the ones provided further up the tree, values provided outside of any component are visible everywhere.
+//!
+//! Provided values are not tracked, to share data that changes, provide an [arena::Ref] to it.
+
+use crate::*;
+
+use std::any::{ Any, TypeId };
+use std::collections::HashMap;
+use std::rc::Rc;
+
+
+#[derive(Default)]
+pub(crate) struct Contexts {
+	/// The values provided by each container, or outside of any container.
+	provided: HashMap<Option<ContainerId>, HashMap<TypeId, Rc<dyn Any>>>,
+	/// The containers that are currently being built, laid out or drawn, innermost last.
+	scopes: Vec<ContainerId>,
+}
+
+
+impl State {
+	/// Provide `value` to the component that is currently being built and its descendants.
+	/// 
+	/// Replaces a value of the same type provided by the same component.
+	/// Values provided outside of any component are visible to all components.
+	/// 
+	/// Example:
+	/// ```rust
+	/// use unison::*;
+	/// 
+	/// #[derive(Clone)]
+	/// struct Theme { accent: Color }
+	/// 
+	/// let mut state = State::new();
+	/// state.provide(Theme { accent: Color(1.0, 0.0, 0.0, 1.0) });
+	/// 
+	/// assert_eq!(state.context::<Theme>().unwrap().accent, Color(1.0, 0.0, 0.0, 1.0));
+	/// ```
+	pub fn provide<T: 'static>(&mut self, value: T) {
+		let mut contexts = self.contexts.borrow_mut();
+		let provider = contexts.scopes.last().copied();
+
+		contexts.provided.entry(provider).or_default().insert(TypeId::of::<T>(), Rc::new(value));
+	}
+
+	/// Look up the value of type `T` provided by the closest ancestor of the component
+	/// that is currently being built, laid out or drawn (including the component itself).
+	pub fn context<T: Clone + 'static>(&self) -> Option<T> {
+		let contexts = self.contexts.borrow();
+
+		let providers = contexts.scopes.iter().rev()
+			.map(|id| Some(*id))
+			.chain(std::iter::once(None));
+
+		providers
+			.filter_map(|provider| contexts.provided.get(&provider)?.get(&TypeId::of::<T>()))
+			.find_map(|val| val.downcast_ref::<T>().cloned())
+	}
+
+	/// Run `f` within the scope of the container `id`, so that it can see the values the container provides.
+	pub(crate) fn scoped<R, F: FnOnce() -> R>(&self, id: ContainerId, f: F) -> R {
+		self.contexts.borrow_mut().scopes.push(id);
+		let r = f();
+		self.contexts.borrow_mut().scopes.pop();
+		r
+	}
+
+	/// Like [State::scoped], but passes the [State] to `f` mutably.
+	pub(crate) fn scoped_mut<R, F: FnOnce(&mut State) -> R>(&mut self, id: ContainerId, f: F) -> R {
+		self.contexts.borrow_mut().scopes.push(id);
+		let r = f(self);
+		self.contexts.borrow_mut().scopes.pop();
+		r
+	}
+
+	/// Forget the values provided by the container `id`.
+	pub(crate) fn remove_provided(&mut self, id: ContainerId) {
+		self.contexts.borrow_mut().provided.remove(&Some(id));
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Clone, Copy, PartialEq, Debug)]
+	struct Theme(Color);
+
+	/// Provides a theme to its child, after recording the one it sees itself.
+	struct Provider<T> {
+		theme: Option<Theme>,
+		seen: arena::Ref<Vec<Option<Theme>>>,
+		child: fn(arena::Ref<Vec<Option<Theme>>>) -> T,
+	}
+
+	impl<T: ComponentLike + container::Containable + 'static> Component for Provider<T> {
+		type Child = T;
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			let seen = state.context::<Theme>();
+			state.modify(self.seen, |s| s.push(seen));
+
+			if let Some(theme) = self.theme {
+				state.provide(theme);
+			}
+
+			(self.child)(self.seen)
+		}
+	}
+
+	/// Fills itself with the theme color.
+	struct Themed(arena::Ref<Vec<Option<Theme>>>);
+
+	impl Component for Themed {
+		type Child = ();
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			let seen = state.context::<Theme>();
+			state.modify(self.0, |s| s.push(seen));
+		}
+
+		fn draw<'a, B: Backend>(&self, state: &State, view: &mut B::View<'a>, _: &mut FontState) {
+			if let Some(Theme(color)) = state.context::<Theme>() {
+				view.fill(color.into());
+			}
+		}
+	}
+
+	const RED: Theme = Theme(Color(1.0, 0.0, 0.0, 1.0));
+	const BLUE: Theme = Theme(Color(0.0, 0.0, 1.0, 1.0));
+
+	#[test]
+	fn test_context() {
+		let mut state = State::new();
+		let seen = state.alloc(Vec::new());
+
+		let root = Provider {
+			theme: Some(RED),
+			seen,
+			child: |seen| (
+				Themed(seen),
+				Provider { theme: Some(BLUE), seen, child: Themed },
+				Provider { theme: None, seen, child: Themed },
+			),
+		};
+
+		let _tree = container::ComponentTree::new(root, &mut state);
+
+		assert_eq!(state.get(seen).unwrap(), &[
+			None, // the root itself
+			Some(RED),
+			Some(RED), Some(BLUE), // shadowed
+			Some(RED), Some(RED),
+		]);
+
+		// nothing is in scope outside of the tree
+		assert_eq!(state.context::<Theme>(), None);
+	}
+
+	#[test]
+	fn test_context_in_draw() {
+		let mut bcknd = unison_backend_soft::SoftBackend::new();
+		let mut font_state = FontState::new();
+
+		struct Root;
+
+		impl Component for Root {
+			type Child = (Themed, Provider<Themed>);
+
+			fn build(&self, state: &mut State) -> Self::Child {
+				state.provide(RED);
+
+				let seen = state.alloc(Vec::new());
+				(Themed(seen), Provider { theme: Some(BLUE), seen, child: Themed })
+			}
+		}
+
+		let mut page = Page::new(Root);
+
+		let img = page.render_offscreen(&mut bcknd, (4, 1), &mut font_state).to_image();
+
+		assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+		assert_eq!(img.get_pixel(3, 0).0, [0, 0, 255, 255]);
+	}
+}
@@ -1,12 +1,32 @@
 use crate::*;
 
-use std::collections::HashMap;
+use std::any::Any;
+use std::collections::{ HashMap, HashSet };
+
+
+type Handler = Box<dyn FnMut(&dyn Any, &mut State)>;
 
 
 pub struct EventState {
 	event_names: HashMap<&'static str, usize>,
 	event_types: Vec<std::any::TypeId>,
 	event_buffer: misc::RingBuffer<Event>,
+
+	/// The subscribers of each event type, indexed like `event_types`.
+	subscribers: Vec<Vec<(Subscription, Handler)>>,
+	next_subscription: usize,
+	/// The event types whose handlers are taken out for dispatching, innermost last.
+	dispatching: Vec<usize>,
+	/// Subscriptions that were removed while their handlers were taken out for dispatching.
+	removed: HashSet<Subscription>,
+	/// The subscriptions that belong to each container, see [State::subscribe].
+	owned: HashMap<ContainerId, Vec<Subscription>>,
+}
+
+impl Default for EventState {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl EventState {
@@ -15,41 +35,317 @@ impl EventState {
 			event_names: HashMap::with_capacity(64),
 			event_types: Vec::with_capacity(64),
 			event_buffer: misc::RingBuffer::new(64),
+
+			subscribers: Vec::with_capacity(64),
+			next_subscription: 0,
+			dispatching: Vec::new(),
+			removed: HashSet::new(),
+			owned: HashMap::new(),
 		}
 	}
 
+	/// Get the [EventType] registered under `name`, registering it if it does not exist yet.
+	/// 
+	/// Panics if `name` has already been registered with a different type.
 	pub fn get_event_type<T: 'static>(&mut self, name: &'static str) -> EventType<T> {
 		let typeid = std::any::TypeId::of::<T>();
 		if let Some(ty) = self.event_names.get(name) {
 			if typeid != self.event_types[*ty] {
-				panic!("wrong type"); // TODO
+				panic!("event type `{}` has already been registered with a different type", name);
 			}
 
 			return EventType::new(*ty);
 		}
 
+		let ty = self.event_types.len();
+
 		self.event_types.push(typeid);
-		EventType::new(self.event_types.len() - 1)
+		self.subscribers.push(Vec::new());
+		self.event_names.insert(name, ty);
+
+		EventType::new(ty)
 	}
 
 	pub fn emit<T: 'static>(&mut self, ty: EventType<T>, val: T) {
 		self.event_buffer.push(Event::new(ty, val))
 	}
+
+	/// Call `handler` for every event of type `ty` that gets dispatched.
+	pub fn subscribe<T, F>(&mut self, ty: EventType<T>, handler: F) -> Subscription where
+		T: 'static,
+		F: FnMut(&T, &mut State) + 'static,
+	{
+		self.subscribe_owned(ty, None, handler)
+	}
+
+	/// Like [EventState::subscribe], but the subscription belongs to the container `owner`.
+	pub(crate) fn subscribe_owned<T, F>(&mut self, ty: EventType<T>, owner: Option<ContainerId>, mut handler: F) -> Subscription where
+		T: 'static,
+		F: FnMut(&T, &mut State) + 'static,
+	{
+		let sub = Subscription { ty: ty.0, id: self.next_subscription, owner };
+		self.next_subscription += 1;
+
+		if let Some(owner) = owner {
+			self.owned.entry(owner).or_default().push(sub);
+		}
+
+		let handler: Handler = Box::new(move |val, state| {
+			// events are always created from an EventType<T>, so the payload has type T
+			handler(val.downcast_ref::<T>().unwrap(), state)
+		});
+
+		self.subscribers[ty.0].push((sub, handler));
+
+		sub
+	}
+
+	/// Remove a [Subscription], so that its handler does not get called anymore.
+	pub fn unsubscribe(&mut self, sub: Subscription) {
+		if let Some(owned) = sub.owner.and_then(|owner| self.owned.get_mut(&owner)) {
+			owned.retain(|s| *s != sub);
+		}
+
+		let subscribers = &mut self.subscribers[sub.ty];
+
+		match subscribers.iter().position(|(s, _)| *s == sub) {
+			Some(idx) => drop(subscribers.remove(idx)),
+			// the handlers are currently being dispatched to, otherwise it has already been removed
+			None => if self.dispatching.contains(&sub.ty) {
+				self.removed.insert(sub);
+			},
+		}
+	}
+
+	/// Remove all subscriptions that belong to the container `id`.
+	pub(crate) fn remove_owned_subscriptions(&mut self, id: ContainerId) {
+		for sub in self.owned.remove(&id).unwrap_or_default() {
+			self.unsubscribe(sub);
+		}
+	}
+
+	fn pop_event(&mut self) -> Option<Event> {
+		self.event_buffer.pop_bottom()
+	}
+
+	fn take_subscribers(&mut self, ty: usize) -> Vec<(Subscription, Handler)> {
+		self.dispatching.push(ty);
+		std::mem::take(&mut self.subscribers[ty])
+	}
+
+	/// Put back the subscribers taken by [EventState::take_subscribers],
+	/// keeping those that subscribed in the meantime.
+	fn restore_subscribers(&mut self, ty: usize, mut taken: Vec<(Subscription, Handler)>) {
+		self.dispatching.pop();
+
+		if !self.removed.is_empty() {
+			taken.retain(|(s, _)| !self.removed.remove(s));
+
+			// the type might be dispatched by an outer call as well
+			if !self.dispatching.contains(&ty) {
+				self.removed.retain(|s| s.ty != ty);
+			}
+		}
+
+		let added = std::mem::replace(&mut self.subscribers[ty], taken);
+		self.subscribers[ty].extend(added);
+	}
+}
+
+impl State {
+	/// Pass all emitted events to their subscribers, in the order they were emitted.
+	/// 
+	/// Events emitted by the handlers are dispatched within the same call.
+	pub fn dispatch_events(&mut self) {
+		while let Some(Event(ty, val)) = self.event_state.pop_event() {
+			let mut subscribers = self.event_state.take_subscribers(ty);
+
+			for (sub, handler) in subscribers.iter_mut() {
+				// the subscription might have been removed by a previous handler
+				if !self.event_state.removed.contains(sub) {
+					handler(&*val, self);
+				}
+			}
+
+			self.event_state.restore_subscribers(ty, subscribers);
+		}
+	}
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct EventType<T>(usize, std::marker::PhantomData<T>);
 
 impl<T> EventType<T> {
-	pub fn new(ty: usize) -> Self {
+	/// Only [EventState::get_event_type] creates event types, so that the type of their events is known.
+	pub(crate) fn new(ty: usize) -> Self {
 		Self(ty, std::marker::PhantomData)
 	}
 }
 
-pub struct Event(usize, Box<dyn std::any::Any>);
+/// A handle to a handler subscribed to an [EventType].
+/// 
+/// Pass it to [State::unsubscribe] to remove the handler again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Subscription {
+	ty: usize,
+	id: usize,
+	owner: Option<ContainerId>,
+}
+
+pub struct Event(usize, Box<dyn Any>);
 
 impl Event {
 	pub fn new<T: 'static>(ty: EventType<T>, val: T) -> Self {
 		Self(ty.0, Box::new(val))
 	}
 }
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_event_types() {
+		let mut state = EventState::new();
+
+		let a = state.get_event_type::<u32>("a");
+		let b = state.get_event_type::<u32>("b");
+
+		assert_eq!(state.get_event_type::<u32>("a"), a);
+		assert_ne!(a, b);
+	}
+
+	#[test]
+	#[should_panic]
+	fn test_event_type_mismatch() {
+		let mut state = EventState::new();
+
+		state.get_event_type::<u32>("a");
+		state.get_event_type::<i64>("a");
+	}
+
+	#[test]
+	fn test_dispatch_events() {
+		let mut state = State::new();
+
+		let ty = state.get_event_type::<u32>("add");
+		let other = state.get_event_type::<()>("other");
+		let sum = state.alloc(0u32);
+		let calls = state.alloc(0u32);
+
+		let sub = state.subscribe(ty, move |val, state| { state.mutate_ref(sum, |s| *s += *val); });
+		state.subscribe(ty, move |_, state| { state.mutate_ref(calls, |c| *c += 1); });
+
+		// handlers can emit events themselves
+		state.subscribe(other, move |_, state| state.emit(ty, 10));
+
+		state.emit(ty, 1);
+		state.emit(ty, 2);
+		state.emit(other, ());
+
+		assert_eq!(state.get(sum), Some(&0));
+
+		state.dispatch_events();
+		assert_eq!(state.get(sum), Some(&13));
+		assert_eq!(state.get(calls), Some(&3));
+
+		state.unsubscribe(sub);
+		// already removed, nothing to remember
+		state.unsubscribe(sub);
+		assert!(state.event_state.removed.is_empty());
+
+		state.emit(ty, 5);
+		state.dispatch_events();
+
+		assert_eq!(state.get(sum), Some(&13));
+		assert_eq!(state.get(calls), Some(&4));
+	}
+
+	#[test]
+	fn test_unsubscribe_while_dispatching() {
+		let mut state = State::new();
+
+		let ty = state.get_event_type::<()>("ev");
+		let calls = state.alloc(0u32);
+
+		let second = std::rc::Rc::new(std::cell::Cell::new(None));
+
+		let s = second.clone();
+		state.subscribe(ty, move |_, state| {
+			if let Some(sub) = s.take() {
+				state.unsubscribe(sub);
+			}
+		});
+
+		second.set(Some(state.subscribe(ty, move |_, state| { state.mutate_ref(calls, |c| *c += 1); })));
+
+		state.emit(ty, ());
+		state.emit(ty, ());
+		state.dispatch_events();
+
+		assert_eq!(state.get(calls), Some(&0));
+		assert!(state.event_state.removed.is_empty());
+	}
+
+	/// Counts the events of type `ty`, rebuilt whenever `rebuild` changes.
+	#[derive(Clone, Copy)]
+	struct Listener {
+		ty: EventType<()>,
+		rebuild: arena::Ref<u32>,
+		calls: arena::Ref<u32>,
+	}
+
+	impl Component for Listener {
+		type Child = ();
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			state.get(self.rebuild);
+
+			let calls = self.calls;
+			state.subscribe(self.ty, move |_, state| { state.mutate_ref(calls, |c| *c += 1); });
+		}
+	}
+
+	struct Root {
+		show: arena::Ref<bool>,
+		listener: Listener,
+	}
+
+	impl Component for Root {
+		type Child = Option<Listener>;
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			state.get(self.show).unwrap().then_some(self.listener)
+		}
+	}
+
+	#[test]
+	fn test_owned_subscriptions() {
+		let mut state = State::new();
+
+		let ty = state.get_event_type::<()>("ev");
+		let (show, rebuild, calls) = (state.alloc(true), state.alloc(0), state.alloc(0));
+
+		let listener = Listener { ty, rebuild, calls };
+		let mut tree = container::ComponentTree::new(Root { show, listener }, &mut state);
+
+		// the subscription made by the previous build is replaced
+		state.set(rebuild, 1);
+		let dirty = state.take_dirty();
+		tree.update(&mut state, &dirty);
+
+		state.emit(ty, ());
+		state.dispatch_events();
+		assert_eq!(state.get(calls), Some(&1));
+
+		// and removed together with the container
+		state.set(show, false);
+		let dirty = state.take_dirty();
+		tree.update(&mut state, &dirty);
+
+		state.emit(ty, ());
+		state.dispatch_events();
+		assert_eq!(state.get(calls), Some(&1));
+	}
+}
@@ -22,9 +22,25 @@ impl FontState {
 		}
 	}
 
+	/// Create a [FontState] that only knows the given fonts instead of the ones installed on the system,
+	/// so that text is laid out and drawn the same on every machine.
+	pub fn with_fonts<I: IntoIterator<Item = Vec<u8>>>(fonts: I) -> Self {
+		let mut db = cosmic_text::fontdb::Database::new();
+
+		for data in fonts {
+			db.load_font_data(data);
+		}
+
+		Self {
+			font_system: cosmic_text::FontSystem::new_with_locale_and_db(String::from("en-US"), db),
+			swash_cache: cosmic_text::SwashCache::new(),
+			fonts: HashMap::new(),
+		}
+	}
+
 	pub fn ensure_font<B: Backend>(&mut self, id: ID, size: f32, bcknd: &mut B) -> FontId {
-		// SAFETY: we're store it as a u32 to be able to hash and compare it.
-		let fid = FontId(id, unsafe { std::mem::transmute(size) });
+		// store it as a u32 to be able to hash and compare it.
+		let fid = FontId(id, size.to_bits());
 
 		if !self.fonts.contains_key(&fid) {
 			let f = self.font_system.get_font(id).unwrap();
@@ -48,6 +64,12 @@ impl FontState {
 	}
 }
 
+impl Default for FontState {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 pub struct Font {
 	font: Arc<cosmic_text::Font>,
 	size: f32,
@@ -93,10 +115,7 @@ impl Font {
 								left: img.placement.left,
 								top: img.placement.top,
 
-								is_colored: match img.content {
-									cosmic_text::SwashContent::Color => true,
-									_ => false,
-								}
+								is_colored: matches!(img.content, cosmic_text::SwashContent::Color),
 							});
 							break;
 						}
@@ -113,6 +132,7 @@ impl Font {
 	}
 }
 
+#[allow(dead_code)]
 fn save(cp: &CachePage) {
 	let mut buf = image::ImageBuffer::<image::Rgba<f32>, Vec<_>>::new(PAGE_SIZE, PAGE_SIZE);
 	buf.copy_from_slice(bytemuck::cast_slice(cp.tex.as_bytes()));
@@ -127,7 +147,7 @@ pub struct FontId(pub ID, u32);
 
 impl FontId {
 	pub fn size(&self) -> f32 {
-		unsafe { std::mem::transmute(self.1) }
+		f32::from_bits(self.1)
 	}
 }
 
@@ -0,0 +1,138 @@
+//! Updating a [State] from other threads.
+//!
+//! A [StateHandle] queues operations that are applied on the UI thread
+//! the next time the event loop runs, see [State::handle].
+
+use crate::*;
+
+use std::sync::mpsc;
+
+
+/// An operation queued by a [StateHandle].
+pub(crate) type RemoteOp = Box<dyn FnOnce(&mut State) + Send>;
+
+
+/// A cloneable handle to a [State] that can be sent to other threads.
+///
+/// Operations are applied on the UI thread in the order they were queued,
+/// triggering the same redraw logic as their counterparts on [State].
+/// They are discarded if the [State] has been dropped.
+///
+/// Example:
+/// ```rust
+/// use unison::*;
+///
+/// let mut state = State::new();
+///
+/// let rows = state.alloc(Vec::<String>::new());
+/// let handle = state.handle();
+///
+/// std::thread::spawn(move || {
+///     handle.set(rows, vec![String::from("loaded")]);
+/// }).join().unwrap();
+///
+/// state.apply_remote();
+/// assert_eq!(state.get(rows).unwrap().len(), 1);
+/// ```
+#[derive(Clone)]
+pub struct StateHandle {
+	sender: mpsc::Sender<RemoteOp>,
+}
+
+impl StateHandle {
+	pub(crate) fn new(sender: mpsc::Sender<RemoteOp>) -> Self {
+		Self { sender }
+	}
+
+	/// Queue an arbitrary operation.
+	pub fn run<F: FnOnce(&mut State) + Send + 'static>(&self, op: F) {
+		if self.sender.send(Box::new(op)).is_ok() {
+			runtime::wake_event_loop();
+		}
+	}
+
+	/// Queue a [State::set].
+	pub fn set<T: PartialEq + Send + 'static>(&self, r: arena::Ref<T>, val: T) {
+		self.run(move |state| { state.set(r, val); })
+	}
+
+	/// Queue a [State::mutate_ref].
+	pub fn mutate_ref<T, F>(&self, r: arena::Ref<T>, op: F) where
+		T: Clone + PartialEq + 'static,
+		F: FnOnce(&mut T) + Send + 'static,
+	{
+		self.run(move |state| { state.mutate_ref(r, op); })
+	}
+
+	/// Queue a [State::emit].
+	pub fn emit<T: Send + 'static>(&self, ty: EventType<T>, val: T) {
+		self.run(move |state| state.emit(ty, val))
+	}
+}
+
+
+impl State {
+	/// Get a [StateHandle] to update this [State] from other threads.
+	pub fn handle(&self) -> StateHandle {
+		StateHandle::new(self.remote.0.clone())
+	}
+
+	/// Apply all operations queued by [StateHandle]s.
+	///
+	/// Returns `true` if there were any.
+	pub fn apply_remote(&mut self) -> bool {
+		let mut applied = false;
+
+		while let Ok(op) = self.remote.1.try_recv() {
+			op(self);
+			applied = true;
+		}
+
+		applied
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_remote_updates() {
+		let mut state = State::new();
+
+		let count = state.alloc(0u32);
+		let ty = state.get_event_type::<u32>("loaded");
+		let received = state.alloc(0u32);
+
+		state.subscribe(ty, move |val, state| { state.set(received, *val); });
+
+		let id = ContainerId::new();
+		state.track(id, Phase::Draw, || state.get(count));
+
+		let handles: Vec<_> = (0..4).map(|_| state.handle()).collect();
+
+		let threads: Vec<_> = handles.into_iter()
+			.map(|h| std::thread::spawn(move || h.mutate_ref(count, |c| *c += 1)))
+			.collect();
+
+		for t in threads {
+			t.join().unwrap();
+		}
+
+		let handle = state.handle();
+		std::thread::spawn(move || handle.emit(ty, 7)).join().unwrap();
+
+		// nothing happens until the operations are applied on this thread
+		assert_eq!(state.get(count), Some(&0));
+
+		assert!(state.apply_remote());
+		assert!(!state.apply_remote());
+
+		assert_eq!(state.get(count), Some(&4));
+		assert_eq!(state.is_dirty(id), Some(Dirty::Redraw));
+
+		state.dispatch_events();
+		assert_eq!(state.get(received), Some(&7));
+	}
+}
@@ -0,0 +1,313 @@
+//! Undo and redo.
+//!
+//! Changes to values that have been registered using [State::enable_history] are recorded,
+//! when made through [State::set] or [State::mutate_ref]. Changes made within [State::transaction]
+//! are undone together, all other changes are undone one by one.
+//!
+//! Changes made through [State::modify] cannot be recorded, since the old value is not known.
+
+use crate::*;
+use crate::arena::RefId;
+
+use std::cell::RefCell;
+use std::collections::{ HashSet, VecDeque };
+use std::rc::Rc;
+
+
+/// Swaps the current value with the recorded one, undoing or redoing a change.
+type SwapFn = Box<dyn FnMut(&mut State)>;
+
+pub(crate) struct History {
+	/// The values whose changes are recorded.
+	recorded: HashSet<RefId>,
+	undo: VecDeque<Vec<SwapFn>>,
+	redo: Vec<Vec<SwapFn>>,
+	limit: usize,
+	/// The transaction that is currently running.
+	open: Option<Vec<SwapFn>>,
+	/// How many calls to [State::transaction] are running.
+	depth: usize,
+}
+
+impl History {
+	pub fn new() -> Self {
+		Self {
+			recorded: HashSet::new(),
+			undo: VecDeque::new(),
+			redo: Vec::new(),
+			limit: 100,
+			open: None,
+			depth: 0,
+		}
+	}
+
+	/// Record a change of `r`, which had the value `old` before.
+	pub fn record<T: 'static>(&mut self, r: arena::Ref<T>, old: Rc<RefCell<T>>) {
+		if !self.recorded.contains(&r.id()) {
+			return;
+		}
+
+		let swap: SwapFn = Box::new(move |state: &mut State| {
+			if let Some(p) = state.arena.get_mut(r) {
+				std::mem::swap(p, &mut *old.borrow_mut());
+				state.emit_ref_changed(r);
+			}
+		});
+
+		match &mut self.open {
+			Some(transaction) => transaction.push(swap),
+			None => self.commit(vec![swap]),
+		}
+	}
+
+	fn commit(&mut self, transaction: Vec<SwapFn>) {
+		if transaction.is_empty() {
+			return;
+		}
+
+		self.redo.clear();
+		self.undo.push_back(transaction);
+
+		while self.undo.len() > self.limit {
+			self.undo.pop_front();
+		}
+	}
+}
+
+
+impl State {
+	/// Record the changes of the value behind `r`, so that they can be undone.
+	pub fn enable_history<T>(&mut self, r: arena::Ref<T>) {
+		self.history.recorded.insert(r.id());
+	}
+
+	/// Stop recording the changes of the value behind `r`.
+	/// 
+	/// Changes that have already been recorded can still be undone.
+	pub fn disable_history<T>(&mut self, r: arena::Ref<T>) {
+		self.history.recorded.remove(&r.id());
+	}
+
+	/// Set how many transactions can be undone, dropping the oldest ones if there are more.
+	/// 
+	/// Defaults to 100.
+	pub fn set_history_limit(&mut self, limit: usize) {
+		self.history.limit = limit;
+
+		while self.history.undo.len() > limit {
+			self.history.undo.pop_front();
+		}
+	}
+
+	/// Run `f`, grouping all recorded changes into a single transaction, that is undone at once.
+	/// 
+	/// Change notifications are deferred like in [State::batch].
+	/// Nested transactions are part of the outermost one.
+	/// 
+	/// Example:
+	/// ```rust
+	/// use unison::*;
+	/// 
+	/// let mut state = State::new();
+	/// 
+	/// let text = state.alloc(String::new());
+	/// state.enable_history(text);
+	/// 
+	/// state.transaction(|state| {
+	///     state.mutate_ref(text, |t| t.push('a'));
+	///     state.mutate_ref(text, |t| t.push('b'));
+	/// });
+	/// state.set(text, String::from("c"));
+	/// 
+	/// state.undo();
+	/// assert_eq!(state.get(text).unwrap(), "ab");
+	/// 
+	/// state.undo();
+	/// assert_eq!(state.get(text).unwrap(), "");
+	/// 
+	/// state.redo();
+	/// assert_eq!(state.get(text).unwrap(), "ab");
+	/// ```
+	pub fn transaction<R, F: FnOnce(&mut State) -> R>(&mut self, f: F) -> R {
+		self.history.depth += 1;
+		self.history.open.get_or_insert_with(Vec::new);
+
+		let guard = TransactionGuard(self);
+		guard.0.batch(f)
+	}
+
+	/// Undo the last transaction.
+	/// 
+	/// Returns `false` if there is nothing to undo.
+	pub fn undo(&mut self) -> bool {
+		let mut transaction = match self.history.undo.pop_back() {
+			Some(t) => t,
+			None => return false,
+		};
+
+		self.batch(|state| {
+			for swap in transaction.iter_mut().rev() {
+				swap(state);
+			}
+		});
+
+		self.history.redo.push(transaction);
+		true
+	}
+
+	/// Redo the last undone transaction.
+	/// 
+	/// Returns `false` if there is nothing to redo.
+	pub fn redo(&mut self) -> bool {
+		let mut transaction = match self.history.redo.pop() {
+			Some(t) => t,
+			None => return false,
+		};
+
+		self.batch(|state| {
+			for swap in transaction.iter_mut() {
+				swap(state);
+			}
+		});
+
+		self.history.undo.push_back(transaction);
+		true
+	}
+
+	pub fn can_undo(&self) -> bool {
+		!self.history.undo.is_empty()
+	}
+
+	pub fn can_redo(&self) -> bool {
+		!self.history.redo.is_empty()
+	}
+
+	/// Forget all recorded changes.
+	pub fn clear_history(&mut self) {
+		self.history.undo.clear();
+		self.history.redo.clear();
+	}
+}
+
+
+/// Ends a [State::transaction] when dropped, even if `f` panics.
+///
+/// The changes made before the panic have happened, so they are committed as well.
+struct TransactionGuard<'a>(&'a mut State);
+
+impl Drop for TransactionGuard<'_> {
+	fn drop(&mut self) {
+		let history = &mut self.0.history;
+		history.depth -= 1;
+
+		if history.depth == 0 {
+			let transaction = history.open.take().unwrap();
+			history.commit(transaction);
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_undo_redo() {
+		let mut state = State::new();
+
+		let a = state.alloc(0);
+		let b = state.alloc(0);
+		let untracked = state.alloc(0);
+
+		state.enable_history(a);
+		state.enable_history(b);
+
+		state.set(a, 1);
+		state.transaction(|state| {
+			state.set(a, 2);
+			state.set(b, 1);
+			state.mutate_ref(a, |a| *a += 1);
+			state.set(untracked, 1);
+		});
+
+		assert!(state.can_undo());
+		assert!(!state.can_redo());
+
+		let id = ContainerId::new();
+		state.track(id, Phase::Layout, || state.get(b));
+
+		assert!(state.undo());
+		assert_eq!((state.get(a), state.get(b)), (Some(&1), Some(&0)));
+		assert_eq!(state.get(untracked), Some(&1));
+
+		// readers are notified as if the change was made by the user
+		assert_eq!(state.is_dirty(id), Some(Dirty::Layout));
+		assert!(state.request_redraw);
+
+		assert!(state.undo());
+		assert_eq!(state.get(a), Some(&0));
+		assert!(!state.undo());
+
+		assert!(state.redo());
+		assert!(state.redo());
+		assert_eq!((state.get(a), state.get(b)), (Some(&3), Some(&1)));
+		assert!(!state.redo());
+
+		// a new change drops the redo history
+		state.undo();
+		state.set(b, 5);
+		assert!(!state.can_redo());
+
+		state.undo();
+		assert_eq!((state.get(a), state.get(b)), (Some(&1), Some(&0)));
+	}
+
+	#[test]
+	fn test_transaction_panic() {
+		let mut state = State::new();
+
+		let a = state.alloc(0);
+		state.enable_history(a);
+
+		let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+			state.transaction(|state| {
+				state.set(a, 1);
+				state.set(a, 2);
+				panic!("oops");
+			})
+		}));
+		assert!(result.is_err());
+
+		// the transaction is closed, later changes are undone on their own
+		state.set(a, 3);
+
+		assert!(state.undo());
+		assert_eq!(state.get(a), Some(&2));
+		assert!(state.undo());
+		assert_eq!(state.get(a), Some(&0));
+		assert!(!state.undo());
+	}
+
+	#[test]
+	fn test_history_limit() {
+		let mut state = State::new();
+
+		let a = state.alloc(0);
+		state.enable_history(a);
+
+		for i in 1..=5 {
+			state.set(a, i);
+		}
+
+		state.set_history_limit(3);
+
+		while state.undo() {}
+		assert_eq!(state.get(a), Some(&2));
+
+		state.disable_history(a);
+		state.set(a, 10);
+		assert!(!state.can_undo());
+		assert!(state.can_redo());
+	}
+}
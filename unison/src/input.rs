@@ -0,0 +1,90 @@
+use winit::event::{ WindowEvent, ElementState, MouseScrollDelta };
+
+pub use winit::event::{ MouseButton, VirtualKeyCode, ModifiersState, TouchPhase };
+
+
+/// How many pixels a single line of scrolling moves.
+pub const SCROLL_LINE_HEIGHT: f32 = 16.0;
+
+
+/// Input from mouse, keyboard and touch devices.
+///
+/// Positions are given in window coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+	PointerMoved { pos: (f32, f32) },
+	/// The pointer has left the window.
+	PointerLeft,
+	PointerPressed { pos: (f32, f32), button: MouseButton },
+	PointerReleased { pos: (f32, f32), button: MouseButton },
+	/// Scrolling by the given amount of pixels.
+	Scroll { pos: (f32, f32), delta: (f32, f32) },
+	KeyPressed { key: Option<VirtualKeyCode>, scancode: u32, modifiers: ModifiersState },
+	KeyReleased { key: Option<VirtualKeyCode>, scancode: u32, modifiers: ModifiersState },
+	/// A character has been typed.
+	Text(char),
+	Touch { id: u64, phase: TouchPhase, pos: (f32, f32) },
+}
+
+impl InputEvent {
+	/// Get the position of the event, if it is tied to a position within the window.
+	pub fn pos(&self) -> Option<(f32, f32)> {
+		match *self {
+			Self::PointerMoved { pos } |
+			Self::PointerPressed { pos, .. } |
+			Self::PointerReleased { pos, .. } |
+			Self::Scroll { pos, .. } |
+			Self::Touch { pos, .. } => Some(pos),
+			_ => None,
+		}
+	}
+}
+
+
+/// Translates [WindowEvent]s of a single window into [InputEvent]s.
+///
+/// Keeps track of the cursor position and the modifier keys, as winit reports them separately.
+#[derive(Debug, Default)]
+pub(crate) struct InputTranslator {
+	cursor_pos: (f32, f32),
+	modifiers: ModifiersState,
+}
+
+impl InputTranslator {
+	pub fn translate(&mut self, ev: &WindowEvent) -> Option<InputEvent> {
+		match ev {
+			WindowEvent::CursorMoved { position, .. } => {
+				self.cursor_pos = (position.x as f32, position.y as f32);
+				Some(InputEvent::PointerMoved { pos: self.cursor_pos })
+			},
+			WindowEvent::CursorLeft { .. } => Some(InputEvent::PointerLeft),
+			WindowEvent::MouseInput { state, button, .. } => Some(match state {
+				ElementState::Pressed => InputEvent::PointerPressed { pos: self.cursor_pos, button: *button },
+				ElementState::Released => InputEvent::PointerReleased { pos: self.cursor_pos, button: *button },
+			}),
+			WindowEvent::MouseWheel { delta, .. } => {
+				let delta = match delta {
+					MouseScrollDelta::LineDelta(x, y) => (x * SCROLL_LINE_HEIGHT, y * SCROLL_LINE_HEIGHT),
+					MouseScrollDelta::PixelDelta(p) => (p.x as f32, p.y as f32),
+				};
+
+				Some(InputEvent::Scroll { pos: self.cursor_pos, delta })
+			},
+			WindowEvent::ModifiersChanged(m) => {
+				self.modifiers = *m;
+				None
+			},
+			WindowEvent::KeyboardInput { input, .. } => Some(match input.state {
+				ElementState::Pressed => InputEvent::KeyPressed { key: input.virtual_keycode, scancode: input.scancode, modifiers: self.modifiers },
+				ElementState::Released => InputEvent::KeyReleased { key: input.virtual_keycode, scancode: input.scancode, modifiers: self.modifiers },
+			}),
+			WindowEvent::ReceivedCharacter(c) => Some(InputEvent::Text(*c)),
+			WindowEvent::Touch(t) => Some(InputEvent::Touch {
+				id: t.id,
+				phase: t.phase,
+				pos: (t.location.x as f32, t.location.y as f32),
+			}),
+			_ => None,
+		}
+	}
+}
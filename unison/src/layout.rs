@@ -0,0 +1,778 @@
+//! Flexbox and grid layout.
+//!
+//! Each layout pass, every container adds a node styled by its [Layout] to a [LayoutEngine],
+//! which computes the geometry of the whole tree at once using [taffy].
+//! The containers then read back their [LayoutBoxes].
+
+use crate::*;
+
+use std::collections::{ HashMap, HashSet };
+
+
+/// A length along one axis.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Length {
+	/// Determined by the content and the other properties.
+	#[default]
+	Auto,
+	Px(u32),
+	/// Percentage (0.0 to 100.0) of the parent's content box.
+	Percent(f32),
+}
+
+/// How items are distributed along the main axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JustifyContent {
+	#[default]
+	Start,
+	End,
+	Center,
+	SpaceBetween,
+	SpaceAround,
+	SpaceEvenly,
+}
+
+/// How items are aligned along the cross axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+	Start,
+	End,
+	Center,
+	#[default]
+	Stretch,
+	Baseline,
+}
+
+/// Whether items wrap into multiple lines when they overflow the main axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlexWrap {
+	#[default]
+	NoWrap,
+	Wrap,
+	WrapReverse,
+}
+
+/// How a component is positioned within its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Position {
+	/// Laid out with its siblings, then moved by its offsets, see [Layout::set_left].
+	#[default]
+	Relative,
+	/// Not laid out with its siblings, but placed at its offsets from the content box of its parent.
+	Absolute,
+	/// Not laid out with its siblings, but placed at its offsets from the window.
+	Fixed,
+}
+
+/// What happens to children that do not fit into a component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+	/// They are drawn outside of the component.
+	#[default]
+	Visible,
+	/// They are cut off at the padding box of the component.
+	Clip,
+	/// They are laid out at their intrinsic size, moved by the offset and cut off at the padding box.
+	/// 
+	/// The offset is clamped to [State::scroll_extent].
+	Scroll(arena::Ref<(f32, f32)>),
+}
+
+/// How a component lays out its children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Display {
+	/// Along the stack orientation, like a CSS flexbox.
+	#[default]
+	Flex,
+	/// In the rows and columns defined by [Layout::set_grid_rows] and [Layout::set_grid_columns].
+	Grid,
+}
+
+/// The size of a row or column of a grid.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Track {
+	/// Sized to the content, but stretched to fill the remaining space.
+	#[default]
+	Auto,
+	Px(u32),
+	/// Percentage (0.0 to 100.0) of the grid's content box.
+	Percent(f32),
+	/// A fraction of the space left by the other tracks.
+	Fr(f32),
+	/// As small as the content allows.
+	MinContent,
+	/// As large as the content wants to be.
+	MaxContent,
+}
+
+/// The rows or columns of a grid.
+/// 
+/// Holds up to [Tracks::MAX] tracks, so that it can be used as a [Value].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Tracks {
+	tracks: [Track; Tracks::MAX],
+	len: usize,
+}
+
+impl Tracks {
+	pub const MAX: usize = 16;
+
+	/// Panics if there are more than [Tracks::MAX] tracks.
+	pub fn new<const N: usize>(tracks: [Track; N]) -> Self {
+		assert!(N <= Self::MAX, "a grid can have at most {} explicit tracks", Self::MAX);
+
+		let mut t = Self::default();
+		t.tracks[..N].copy_from_slice(&tracks);
+		t.len = N;
+		t
+	}
+
+	/// Create `count` equal tracks.
+	pub fn repeat(count: usize, track: Track) -> Self {
+		assert!(count <= Self::MAX, "a grid can have at most {} explicit tracks", Self::MAX);
+
+		Self {
+			tracks: [track; Self::MAX],
+			len: count,
+		}
+	}
+
+	pub fn as_slice(&self) -> &[Track] {
+		&self.tracks[..self.len]
+	}
+}
+
+/// Where a child is placed within the rows or columns of a grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridPlacement {
+	/// The line the child starts at, counting from 1.
+	/// Negative lines count from the end, [None] places the child in the next free cell.
+	pub start: Option<i16>,
+	/// How many tracks the child spans.
+	pub span: u16,
+}
+
+impl Default for GridPlacement {
+	fn default() -> Self {
+		Self { start: None, span: 1 }
+	}
+}
+
+impl GridPlacement {
+	/// Start at the given line.
+	pub fn at(line: i16) -> Self {
+		Self { start: Some(line), span: 1 }
+	}
+
+	/// Span `span` tracks, starting in the next free cell.
+	pub fn span(span: u16) -> Self {
+		Self { start: None, span }
+	}
+
+	pub fn with_span(self, span: u16) -> Self {
+		Self { span, ..self }
+	}
+}
+
+
+impl From<Length> for taffy::Dimension {
+	fn from(value: Length) -> Self {
+		match value {
+			Length::Auto => Self::Auto,
+			Length::Px(v) => Self::Length(v as f32),
+			Length::Percent(p) => Self::Percent(p / 100.0),
+		}
+	}
+}
+
+impl From<Align> for taffy::AlignItems {
+	fn from(value: Align) -> Self {
+		match value {
+			Align::Start => Self::Start,
+			Align::End => Self::End,
+			Align::Center => Self::Center,
+			Align::Stretch => Self::Stretch,
+			Align::Baseline => Self::Baseline,
+		}
+	}
+}
+
+impl From<JustifyContent> for taffy::JustifyContent {
+	fn from(value: JustifyContent) -> Self {
+		match value {
+			JustifyContent::Start => Self::Start,
+			JustifyContent::End => Self::End,
+			JustifyContent::Center => Self::Center,
+			JustifyContent::SpaceBetween => Self::SpaceBetween,
+			JustifyContent::SpaceAround => Self::SpaceAround,
+			JustifyContent::SpaceEvenly => Self::SpaceEvenly,
+		}
+	}
+}
+
+impl From<FlexWrap> for taffy::FlexWrap {
+	fn from(value: FlexWrap) -> Self {
+		match value {
+			FlexWrap::NoWrap => Self::NoWrap,
+			FlexWrap::Wrap => Self::Wrap,
+			FlexWrap::WrapReverse => Self::WrapReverse,
+		}
+	}
+}
+
+impl From<Track> for taffy::TrackSizingFunction {
+	fn from(value: Track) -> Self {
+		use taffy::prelude::*;
+
+		match value {
+			Track::Auto => Self::AUTO,
+			Track::Px(v) => length(v as f32),
+			Track::Percent(p) => percent(p / 100.0),
+			Track::Fr(f) => fr(f),
+			Track::MinContent => Self::MIN_CONTENT,
+			Track::MaxContent => Self::MAX_CONTENT,
+		}
+	}
+}
+
+impl From<GridPlacement> for taffy::Line<taffy::GridPlacement> {
+	fn from(value: GridPlacement) -> Self {
+		use taffy::style_helpers::{ line, span };
+
+		Self {
+			start: value.start.map(line).unwrap_or(taffy::GridPlacement::Auto),
+			end: span(value.span.max(1)),
+		}
+	}
+}
+
+fn offset(v: Option<i32>) -> taffy::LengthPercentageAuto {
+	match v {
+		Some(v) => taffy::LengthPercentageAuto::Length(v as f32),
+		None => taffy::LengthPercentageAuto::Auto,
+	}
+}
+
+fn px(v: u32) -> taffy::LengthPercentage {
+	taffy::LengthPercentage::Length(v as f32)
+}
+
+
+impl Layout {
+	/// Evaluate all properties into a [taffy::Style].
+	pub(crate) fn to_style(&self, state: &State) -> taffy::Style {
+		let margin = self.get_margin(state).unwrap_or_default();
+		let padding = self.get_padding(state).unwrap_or_default();
+
+		let orientation = self.get_stack_orientation(state).unwrap_or_default();
+		let spacing = self.get_stack_spacing(state).unwrap_or(0);
+		let line_spacing = self.get_line_spacing(state).unwrap_or(0);
+
+		let size = |w: Option<Length>, h: Option<Length>| taffy::Size {
+			width: w.unwrap_or_default().into(),
+			height: h.unwrap_or_default().into(),
+		};
+
+		let display = self.get_display(state).unwrap_or_default();
+		let tracks = |t: Option<Tracks>| t.unwrap_or_default().as_slice().iter().map(|t| (*t).into()).collect();
+
+		taffy::Style {
+			display: match display {
+				Display::Flex => taffy::Display::Flex,
+				Display::Grid => taffy::Display::Grid,
+			},
+
+			position: match self.get_position(state).unwrap_or_default() {
+				Position::Relative => taffy::Position::Relative,
+				Position::Absolute | Position::Fixed => taffy::Position::Absolute,
+			},
+			inset: taffy::Rect {
+				left: offset(self.get_left(state).flatten()),
+				right: offset(self.get_right(state).flatten()),
+				top: offset(self.get_top(state).flatten()),
+				bottom: offset(self.get_bottom(state).flatten()),
+			},
+
+			overflow: match self.get_overflow(state).unwrap_or_default() {
+				Overflow::Visible => taffy::Point { x: taffy::Overflow::Visible, y: taffy::Overflow::Visible },
+				Overflow::Clip => taffy::Point { x: taffy::Overflow::Clip, y: taffy::Overflow::Clip },
+				Overflow::Scroll(_) => taffy::Point { x: taffy::Overflow::Scroll, y: taffy::Overflow::Scroll },
+			},
+			scrollbar_width: 0.0,
+
+			flex_direction: match orientation {
+				Orientation::Horizontal => taffy::FlexDirection::Row,
+				Orientation::Vertical => taffy::FlexDirection::Column,
+			},
+			flex_wrap: self.get_flex_wrap(state).unwrap_or_default().into(),
+			flex_grow: self.get_flex(state).unwrap_or(1) as f32,
+			flex_shrink: self.get_flex_shrink(state).unwrap_or(1) as f32,
+			flex_basis: self.get_flex_basis(state).unwrap_or_default().into(),
+
+			size: size(self.get_width(state), self.get_height(state)),
+			min_size: size(self.get_min_width(state), self.get_min_height(state)),
+			max_size: size(self.get_max_width(state), self.get_max_height(state)),
+
+			margin: taffy::Rect {
+				left: px(margin.left).into(),
+				right: px(margin.right).into(),
+				top: px(margin.top).into(),
+				bottom: px(margin.bottom).into(),
+			},
+			padding: taffy::Rect {
+				left: px(padding.left),
+				right: px(padding.right),
+				top: px(padding.top),
+				bottom: px(padding.bottom),
+			},
+
+			justify_content: self.get_justify_content(state).map(Into::into),
+			align_items: self.get_align_items(state).map(Into::into),
+			align_self: self.get_align_self(state).flatten().map(Into::into),
+
+			// the stack spacing is along the main axis, the line spacing between wrapped lines
+			gap: match (display, orientation) {
+				(Display::Grid, _) => taffy::Size {
+					width: px(self.get_column_spacing(state).unwrap_or(0)),
+					height: px(self.get_row_spacing(state).unwrap_or(0)),
+				},
+				(Display::Flex, Orientation::Horizontal) => taffy::Size { width: px(spacing), height: px(line_spacing) },
+				(Display::Flex, Orientation::Vertical) => taffy::Size { width: px(line_spacing), height: px(spacing) },
+			},
+
+			grid_template_columns: tracks(self.get_grid_columns(state)),
+			grid_template_rows: tracks(self.get_grid_rows(state)),
+			grid_column: self.get_grid_column(state).unwrap_or_default().into(),
+			grid_row: self.get_grid_row(state).unwrap_or_default().into(),
+
+			..Default::default()
+		}
+	}
+}
+
+
+impl State {
+	/// Get how far the content scrolled by `offset` can be scrolled along each axis, as of the last layout pass.
+	/// 
+	/// Reading it is tracked like [State::get], so components that depend on it are updated by the next layout pass.
+	/// See [Overflow::Scroll].
+	pub fn scroll_extent(&self, offset: arena::Ref<(f32, f32)>) -> (f32, f32) {
+		self.get(self.scroll_extents).and_then(|e| e.get(&offset.id())).copied().unwrap_or_default()
+	}
+}
+
+
+/// A container whose content can be measured, see [Component::measure].
+pub trait Measure {
+	fn measure(&self, state: &State, available_size: (Option<u32>, Option<u32>), font_state: &mut FontState) -> (u32, u32);
+}
+
+/// Computes the geometry of a tree of containers, see [container::ContainerLike::layout_nodes].
+pub struct LayoutEngine {
+	/// Each node knows the container it belongs to.
+	taffy: taffy::TaffyTree<ContainerId>,
+	/// Nodes placed within the window, see [Position::Fixed].
+	fixed: Vec<taffy::NodeId>,
+	/// The same nodes, to look them up quickly.
+	fixed_set: HashSet<taffy::NodeId>,
+	/// How far the content of each scrolling container can be scrolled, by the [arena::RefId] of its offset.
+	scroll_extents: HashMap<arena::RefId, (f32, f32)>,
+}
+
+impl Default for LayoutEngine {
+	fn default() -> Self {
+		Self {
+			taffy: taffy::TaffyTree::new(),
+			fixed: Vec::new(),
+			fixed_set: HashSet::new(),
+			scroll_extents: HashMap::new(),
+		}
+	}
+}
+
+impl LayoutEngine {
+	pub(crate) fn clear(&mut self) {
+		self.taffy.clear();
+		self.fixed.clear();
+		self.fixed_set.clear();
+		self.scroll_extents.clear();
+	}
+
+	/// Add a node for the container `id` with the given `children`.
+	pub(crate) fn add_node(&mut self, style: taffy::Style, children: &[taffy::NodeId], id: ContainerId) -> taffy::NodeId {
+		let scrolls = style.overflow.x == taffy::Overflow::Scroll;
+
+		// taffy places absolute children relative to the padding box
+		for child in children {
+			let mut child_style = self.taffy.style(*child).unwrap().clone();
+
+			// scrolled content keeps its intrinsic size
+			if scrolls && child_style.flex_shrink != 0.0 {
+				child_style.flex_shrink = 0.0;
+				self.taffy.set_style(*child, child_style.clone()).unwrap();
+			}
+
+			if child_style.position == taffy::Position::Absolute {
+				let shift = |inset: &mut taffy::LengthPercentageAuto, padding: taffy::LengthPercentage| {
+					if let (taffy::LengthPercentageAuto::Length(v), taffy::LengthPercentage::Length(p)) = (*inset, padding) {
+						*inset = taffy::LengthPercentageAuto::Length(v + p);
+					}
+				};
+
+				shift(&mut child_style.inset.left, style.padding.left);
+				shift(&mut child_style.inset.right, style.padding.right);
+				shift(&mut child_style.inset.top, style.padding.top);
+				shift(&mut child_style.inset.bottom, style.padding.bottom);
+
+				self.taffy.set_style(*child, child_style).unwrap();
+			}
+		}
+
+		let node = self.taffy.new_with_children(style, children).unwrap(); // children are always nodes of this tree
+		self.taffy.set_node_context(node, Some(id)).unwrap();
+		node
+	}
+
+	/// Place `node` within the window instead of its parent.
+	pub(crate) fn add_fixed(&mut self, node: taffy::NodeId) {
+		self.fixed.push(node);
+		self.fixed_set.insert(node);
+	}
+
+	/// Lay out `nodes` within a window of the given size.
+	/// 
+	/// Nodes without children are sized by the container with the same id in `measures`.
+	pub(crate) fn compute(&mut self, nodes: &[taffy::NodeId], window_size: (u32, u32), state: &State, font_state: &mut FontState, measures: &HashMap<ContainerId, &dyn Measure>) {
+		let children: Vec<_> = nodes.iter().chain(&self.fixed).copied().collect();
+
+		let root = self.taffy.new_with_children(taffy::Style {
+			size: taffy::Size {
+				width: taffy::Dimension::Length(window_size.0 as f32),
+				height: taffy::Dimension::Length(window_size.1 as f32),
+			},
+			..Default::default()
+		}, &children).unwrap();
+
+		let available = taffy::Size {
+			width: taffy::AvailableSpace::Definite(window_size.0 as f32),
+			height: taffy::AvailableSpace::Definite(window_size.1 as f32),
+		};
+
+		let measure = |known: taffy::Size<Option<f32>>, available: taffy::Size<taffy::AvailableSpace>, _, id: Option<&mut ContainerId>, _: &taffy::Style| {
+			let Some(container) = id.and_then(|id| measures.get(id)) else {
+				return taffy::Size::ZERO;
+			};
+
+			// measure at the smallest size to get the min-content size
+			let limit = |known: Option<f32>, available| known.or(match available {
+				taffy::AvailableSpace::Definite(v) => Some(v),
+				taffy::AvailableSpace::MinContent => Some(0.0),
+				taffy::AvailableSpace::MaxContent => None,
+			}).map(|v: f32| v as u32);
+
+			let (width, height) = container.measure(
+				state,
+				(limit(known.width, available.width), limit(known.height, available.height)),
+				font_state,
+			);
+
+			taffy::Size {
+				width: known.width.unwrap_or(width as f32),
+				height: known.height.unwrap_or(height as f32),
+			}
+		};
+
+		self.taffy.compute_layout_with_measure(root, available, measure).unwrap();
+	}
+
+	/// Get how far the content of the scrolling `node` can be scrolled, and remember it for [State::scroll_extent].
+	pub(crate) fn scroll_extent(&mut self, node: taffy::NodeId, offset: arena::RefId) -> (f32, f32) {
+		let layout = self.taffy.layout(node).unwrap();
+
+		let extent = (
+			(layout.content_size.width - layout.size.width).max(0.0),
+			(layout.content_size.height - layout.size.height).max(0.0),
+		);
+
+		self.scroll_extents.insert(offset, extent);
+		extent
+	}
+
+	/// Take the extents of all scrolling containers, see [LayoutEngine::scroll_extent].
+	pub(crate) fn take_scroll_extents(&mut self) -> HashMap<arena::RefId, (f32, f32)> {
+		std::mem::take(&mut self.scroll_extents)
+	}
+
+	/// Get the boxes of `node`, whose parent's padding box starts at `origin`.
+	pub(crate) fn boxes(&self, node: taffy::NodeId, origin: (i32, i32)) -> LayoutBoxes {
+		// the parent of fixed nodes is the window
+		let origin = if self.fixed_set.contains(&node) { (0, 0) } else { origin };
+
+		let layout = self.taffy.layout(node).unwrap();
+		let style = self.taffy.style(node).unwrap();
+
+		let len = |l: taffy::LengthPercentageAuto| match l {
+			taffy::LengthPercentageAuto::Length(v) => v as u32,
+			_ => 0,
+		};
+
+		let margin = Bounds::new(len(style.margin.top), len(style.margin.left), len(style.margin.bottom), len(style.margin.right));
+		let padding = Bounds::new(
+			layout.padding.top as u32, layout.padding.left as u32,
+			layout.padding.bottom as u32, layout.padding.right as u32,
+		);
+
+		let padding_box = Rect::new(
+			(origin.0 + layout.location.x as i32, origin.1 + layout.location.y as i32),
+			(layout.size.width as u32, layout.size.height as u32),
+		);
+
+		LayoutBoxes::new(padding_box.expand(margin), margin, padding)
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Has a fixed layout and no children.
+	struct Fixed(fn(&mut Layout));
+
+	impl Component for Fixed {
+		type Child = ();
+
+		fn build(&self, _: &mut State) -> Self::Child {}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let mut layout = Layout::new();
+			(self.0)(&mut layout);
+			layout
+		}
+	}
+
+	/// Lays out its children using the given layout.
+	struct Flex<C>(fn(&mut Layout), fn() -> C);
+
+	impl<C: ComponentLike + container::Containable + 'static> Component for Flex<C> {
+		type Child = C;
+
+		fn build(&self, _: &mut State) -> Self::Child {
+			(self.1)()
+		}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let mut layout = Layout::new();
+			(self.0)(&mut layout);
+			layout
+		}
+	}
+
+	/// Measures like a text of `0` characters, 2 pixels wide and 3 pixels high each.
+	struct Text(u32);
+
+	impl Component for Text {
+		type Child = ();
+
+		fn build(&self, _: &mut State) -> Self::Child {}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let mut layout = Layout::new();
+			layout.set_flex(0);
+			layout
+		}
+
+		fn measure(&self, _: &State, available_size: (Option<u32>, Option<u32>), _: &mut FontState) -> (u32, u32) {
+			let per_line = available_size.0.map_or(self.0, |w| (w / 2).max(1));
+			(self.0.min(per_line) * 2, self.0.div_ceil(per_line) * 3)
+		}
+	}
+
+	fn padding_boxes<T: Component>(root: T, window_size: (u32, u32)) -> Vec<Rect> {
+		let mut state = State::new();
+		let mut tree = container::ComponentTree::new(root, &mut state);
+		// labels are measured the same way on every machine
+		tree.layout(&mut state, window_size, &mut FontState::with_fonts([testing::TEST_FONT.to_vec()]));
+
+		// hit test every pixel and collect the boxes of the root's children
+		let mut rects: Vec<Rect> = Vec::new();
+
+		for x in 0..window_size.0 {
+			for y in 0..window_size.1 {
+				if let Some(h) = tree.hit_test((x as f32, y as f32)).get(1) {
+					let rect = tree.get_event_handler(*h).unwrap().boxes().padding_box;
+
+					if !rects.contains(&rect) {
+						rects.push(rect);
+					}
+				}
+			}
+		}
+
+		rects
+	}
+
+	#[test]
+	fn test_fixed_sizes() {
+		let root = Flex(
+			|l| { l.set_justify_content(JustifyContent::SpaceBetween); l.set_align_items(Align::Center); },
+			|| (
+				Fixed(|l| { l.set_flex(0); l.set_width(Length::Px(10)); l.set_height(Length::Px(4)); }),
+				Fixed(|l| { l.set_flex(0); l.set_width(Length::Percent(20.0)); l.set_height(Length::Px(2)); l.set_align_self(Some(Align::End)); }),
+			),
+		);
+
+		let rects = padding_boxes(root, (50, 10));
+
+		assert_eq!(rects, vec![
+			Rect::new((0, 3), (10, 4)),
+			Rect::new((40, 8), (10, 2)),
+		]);
+	}
+
+	#[test]
+	fn test_grow_and_limits() {
+		let root = Flex(
+			|l| l.set_stack_spacing(2),
+			|| (
+				Fixed(|l| l.set_max_width(Length::Px(8))),
+				Fixed(|l| l.set_flex(2)),
+				Fixed(|l| { l.set_flex(0); l.set_flex_basis(Length::Px(6)); l.set_min_width(Length::Px(8)); }),
+			),
+		);
+
+		let rects = padding_boxes(root, (40, 5));
+
+		// 40 - 2 * 2 spacing - 8 fixed = 28, split 1:2 but the first is capped at 8
+		assert_eq!(rects, vec![
+			Rect::new((0, 0), (8, 5)),
+			Rect::new((10, 0), (20, 5)),
+			Rect::new((32, 0), (8, 5)),
+		]);
+	}
+
+	#[test]
+	fn test_wrap() {
+		let root = Flex(
+			|l| { l.set_flex_wrap(FlexWrap::Wrap); l.set_stack_spacing(1); l.set_line_spacing(2); l.set_align_items(Align::Start); },
+			|| vec![
+				Fixed(|l| { l.set_flex(0); l.set_width(Length::Px(6)); l.set_height(Length::Px(3)); }),
+				Fixed(|l| { l.set_flex(0); l.set_width(Length::Px(6)); l.set_height(Length::Px(3)); }),
+				Fixed(|l| { l.set_flex(0); l.set_width(Length::Px(6)); l.set_height(Length::Px(3)); }),
+			],
+		);
+
+		let rects = padding_boxes(root, (14, 8));
+
+		assert_eq!(rects, vec![
+			Rect::new((0, 0), (6, 3)),
+			Rect::new((0, 5), (6, 3)),
+			Rect::new((7, 0), (6, 3)),
+		]);
+	}
+
+	#[test]
+	fn test_position() {
+		let root = Flex(
+			|l| l.set_padding(Bounds::new(2, 2, 2, 2)),
+			|| vec![
+				Fixed(|l| {
+					l.set_position(Position::Absolute);
+					l.set_left(Some(1));
+					l.set_top(Some(1));
+					l.set_width(Length::Px(3));
+					l.set_height(Length::Px(3));
+				}),
+				Fixed(|l| {
+					l.set_position(Position::Fixed);
+					l.set_right(Some(0));
+					l.set_bottom(Some(0));
+					l.set_width(Length::Px(2));
+					l.set_height(Length::Px(2));
+				}),
+				Fixed(|l| { l.set_flex(0); l.set_width(Length::Px(4)); l.set_left(Some(1)); }),
+			],
+		);
+
+		let rects = padding_boxes(root, (20, 10));
+
+		assert_eq!(rects, vec![
+			// moved to the right, but still in the flow
+			Rect::new((3, 2), (4, 6)),
+			// relative to the content box of the parent
+			Rect::new((3, 3), (3, 3)),
+			// relative to the window
+			Rect::new((18, 8), (2, 2)),
+		]);
+	}
+
+	#[test]
+	fn test_measure() {
+		let root = || Flex(|l| l.set_align_items(Align::Start), || (Text(10), Fixed(|l| l.set_align_self(Some(Align::Stretch)))));
+
+		assert_eq!(padding_boxes(root(), (40, 10)), vec![
+			Rect::new((0, 0), (20, 3)),
+			Rect::new((20, 0), (20, 10)),
+		]);
+
+		// the text wraps when it has to shrink
+		assert_eq!(padding_boxes(root(), (12, 10)), vec![
+			Rect::new((0, 0), (12, 6)),
+		]);
+	}
+
+	#[test]
+	fn test_measure_label() {
+		fn label() -> Flex<Label> {
+			// only as high as the label
+			let layout = |l: &mut Layout| {
+				l.set_stack_orientation(Orientation::Vertical);
+				l.set_align_items(Align::Start);
+				l.set_align_self(Some(Align::Start));
+			};
+
+			Flex(layout, || Label { text: String::from("Hello World") })
+		}
+
+		let rects = padding_boxes(label(), (200, 40));
+		assert_eq!(rects.len(), 1);
+		assert_eq!(rects[0].height, 16);
+
+		let width = rects[0].width;
+		assert!(width > 0 && width < 200);
+
+		// one pixel less moves the second word to another line
+		let rects = padding_boxes(label(), (width - 1, 40));
+		assert_eq!(rects[0].height, 32);
+		assert!(rects[0].width < width);
+	}
+
+	#[test]
+	fn test_grid() {
+		let root = Flex(
+			|l| {
+				l.set_display(Display::Grid);
+				l.set_grid_columns(Tracks::new([Track::Px(10), Track::Fr(1.0), Track::Fr(2.0)]));
+				l.set_grid_rows(Tracks::new([Track::Px(4), Track::Fr(1.0)]));
+				l.set_column_spacing(2);
+				l.set_row_spacing(1);
+			},
+			|| vec![
+				Fixed(|_| {}),
+				Fixed(|l| l.set_grid_column(GridPlacement::span(2))),
+				// placed before the automatically placed children
+				Fixed(|l| { l.set_grid_column(GridPlacement::at(2)); l.set_grid_row(GridPlacement::at(2)); }),
+				Fixed(|_| {}),
+			],
+		);
+
+		let rects = padding_boxes(root, (32, 10));
+
+		assert_eq!(rects, vec![
+			Rect::new((0, 0), (10, 4)),
+			Rect::new((0, 5), (10, 5)),
+			Rect::new((12, 0), (20, 4)),
+			Rect::new((12, 5), (6, 5)),
+		]);
+	}
+}
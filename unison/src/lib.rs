@@ -1,5 +1,6 @@
 
 pub(crate) mod container;
+pub use container::LayoutBoxes;
 
 mod component;
 
@@ -14,11 +15,42 @@ pub use reactivity::extra as reactivity_extra; // used for macros
 mod events;
 pub use events::*;
 
+mod input;
+pub use input::*;
+
 mod state;
 pub use state::*;
 
+mod handle;
+pub use handle::*;
+
+mod tasks;
+pub use tasks::*;
+
+mod timers;
+pub use timers::*;
+
+mod animation;
+pub use animation::*;
+
+mod history;
+
+mod persist;
+pub use persist::*;
+
+mod context;
+
+mod layout;
+pub use layout::{ Length, JustifyContent, Align, FlexWrap, Display, Track, Tracks, GridPlacement, Position, Overflow };
+
+pub mod tracking;
+pub use tracking::{ ContainerId, Phase, Dirty };
+
 pub mod misc;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
 mod runtime;
 pub use runtime::*;
 
@@ -16,7 +16,7 @@ pub struct RingBuffer<T> {
 impl<T> RingBuffer<T> {
 	pub fn new(capacity: usize) -> Self {
 		Self {
-			buffer: (0..capacity).into_iter().map(|_| std::mem::MaybeUninit::uninit()).collect(),
+			buffer: (0..capacity).map(|_| std::mem::MaybeUninit::uninit()).collect(),
 			head: 0,
 			tail: 0,
 		}
@@ -37,7 +37,12 @@ impl<T> RingBuffer<T> {
 			self.buffer.insert(self.head, val);
 
 			// all elements to the right will be shifted by one, increase the tail
-			self.tail += 1;
+			if self.tail > self.head {
+				self.tail += 1;
+			}
+
+			// the buffer has grown, so the head cannot wrap around
+			next = self.head + 1;
 		} else {
 			self.buffer[self.head] = val;
 		}
@@ -106,6 +111,12 @@ impl<T> RingBuffer<T> {
 	}
 }
 
+impl<T> Drop for RingBuffer<T> {
+	fn drop(&mut self) {
+		while self.pop_bottom().is_some() {}
+	}
+}
+
 
 #[macro_export]
 macro_rules! impl_tuple {
@@ -147,6 +158,7 @@ mod tests {
 	use super::*;
 
 	#[test]
+	#[allow(clippy::bool_assert_comparison)]
 	fn test_ring_buffer() {
 		let mut buf = RingBuffer::new(4);
 
@@ -268,4 +280,32 @@ mod tests {
 		assert_eq!(buf.is_empty(), true);
 		assert_eq!(buf.is_full(), false);
 	}
+
+	#[test]
+	fn test_ring_buffer_grow_at_end() {
+		let mut buf = RingBuffer::new(4);
+
+		buf.push(0);
+		buf.push(1);
+		buf.push(2);
+		// T
+		//       H
+		// 0 1 2 -
+
+		assert!(buf.is_full());
+
+		buf.push(3);
+		// T
+		//         H
+		// 0 1 2 3 -
+
+		assert_eq!(buf.head, 4);
+		assert_eq!(buf.tail, 0);
+
+		assert_eq!(buf.pop_bottom(), Some(0));
+		assert_eq!(buf.pop_bottom(), Some(1));
+		assert_eq!(buf.pop_bottom(), Some(2));
+		assert_eq!(buf.pop_bottom(), Some(3));
+		assert_eq!(buf.pop_bottom(), None);
+	}
 }
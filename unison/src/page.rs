@@ -1,11 +1,17 @@
 use crate::*;
 use crate::container::ComponentTree;
 
+use std::path::PathBuf;
+
 
 pub struct Page<T: Component> {
 	tree: ComponentTree<T>,
 	title: Option<String>,
 	state: State,
+	/// The size of the last layout pass.
+	layout_size: Option<(u32, u32)>,
+	/// Where persisted values are saved, see [Page::new_persistent].
+	snapshot_path: Option<PathBuf>,
 }
 
 impl<T: Component> Page<T> {
@@ -17,6 +23,40 @@ impl<T: Component> Page<T> {
 			tree,
 			title: None,
 			state,
+			layout_size: None,
+			snapshot_path: None,
+		}
+	}
+
+	/// Create a page, whose persisted values and window geometry are saved to `path` when its window is closed.
+	/// 
+	/// The snapshot is restored before the root component is built, see [State::persist].
+	/// A missing or broken snapshot is ignored, as are values within it that cannot be restored.
+	pub fn new_persistent(root: T, path: impl Into<PathBuf>) -> Self {
+		let path = path.into();
+
+		let mut state = State::new();
+		// nothing has been restored yet, so registering cannot fail
+		let _ = state.persist_window();
+		// the window is still placed if only some other value is broken
+		let _ = state.load_snapshot(&path);
+
+		let tree = ComponentTree::new(root, &mut state);
+
+		Self {
+			tree,
+			title: None,
+			state,
+			layout_size: None,
+			snapshot_path: Some(path),
+		}
+	}
+
+	/// Save all persisted values, if the page has been created using [Page::new_persistent].
+	pub fn save_snapshot(&self) -> Result<(), PersistError> {
+		match &self.snapshot_path {
+			Some(path) => self.state.save_snapshot(path),
+			None => Ok(()),
 		}
 	}
 
@@ -25,21 +65,100 @@ impl<T: Component> Page<T> {
 		self
 	}
 
-	pub fn draw<B: Backend>(&self, surface: &mut B::Surface, bcknd: &mut B, font_state: &mut FontState) {
+	pub fn draw<B: Backend>(&mut self, surface: &mut B::Surface, bcknd: &mut B, font_state: &mut FontState) {
 		let mut view = bcknd.create_view(surface);
+
+		self.state.run_frame_callbacks(std::time::Instant::now());
+		// effects that keep triggering each other continue with the next frame
+		let _ = self.state.run_effects();
+		// changes made by the callbacks are part of this frame
+		self.state.request_redraw = false;
+
+		let size = view.viewport_size();
+		let dirty = self.state.take_dirty();
+
+		if dirty.values().any(|d| *d == Dirty::Rebuild) {
+			self.tree.update(&mut self.state, &dirty);
+		}
+
+		if self.layout_size != Some(size) || dirty.values().any(|d| *d >= Dirty::Layout) {
+			self.tree.layout(&mut self.state, size, font_state);
+			self.layout_size = Some(size);
+		}
+
 		self.tree.draw::<B>(&self.state, &mut view, font_state);
 		view.submit();
+
+		// keep drawing while animating
+		self.state.request_redraw |= self.state.has_frame_callbacks();
+	}
+
+	/// Render the page without a window.
+	/// 
+	/// The page is drawn into an offscreen [Surface] of the given size, which is then read back into a [Texture].
+	pub fn render_offscreen<B: Backend>(&mut self, bcknd: &mut B, size: (u32, u32), font_state: &mut FontState) -> Texture {
+		self.state.set(self.state.window_size, size);
+
+		let mut surface = bcknd.create_offscreen_surface(size);
+		self.draw::<B>(&mut surface, bcknd, font_state);
+
+		surface.read_pixels(bcknd).unwrap() // offscreen surfaces can always be read
 	}
 
 	pub fn update_window(&self, win: &mut winit::window::Window) {
 		if let Some(title) = &self.title {
 			win.set_title(title);
 		}
+
+		// place the window where it was closed
+		if self.state.is_restored("window_size") {
+			if let Some(&(width, height)) = self.state.get(self.state.window_normal_size).filter(|s| s.0 > 0 && s.1 > 0) {
+				win.set_inner_size(winit::dpi::PhysicalSize::new(width, height));
+			}
+		}
+
+		if self.state.is_restored("window_pos") {
+			if let Some(&(x, y)) = self.state.get(self.state.window_pos) {
+				win.set_outer_position(winit::dpi::PhysicalPosition::new(x, y));
+			}
+		}
+
+		if self.state.is_restored("window_maximized") {
+			if let Some(&maximized) = self.state.get(self.state.window_maximized) {
+				win.set_maximized(maximized);
+			}
+		}
+	}
+
+	/// Get the geometry of all containers under `pos` (outermost first), as of the last draw.
+	pub fn hit_test(&mut self, pos: (f32, f32)) -> Vec<LayoutBoxes> {
+		let path = self.tree.hit_test(pos);
+
+		path.into_iter()
+			.filter_map(|handler| self.tree.get_event_handler(handler).map(|h| h.boxes()))
+			.collect()
+	}
+
+	/// Get a [StateHandle] to update the state of this page from other threads.
+	pub fn handle(&self) -> StateHandle {
+		self.state.handle()
+	}
+
+	/// Pass all events emitted within this page to their subscribers.
+	pub fn dispatch_events(&mut self) {
+		self.state.dispatch_events();
+	}
+
+	/// Pass an [InputEvent] to the components of this page.
+	/// 
+	/// Returns `true` if a component handled the event.
+	pub fn dispatch_input(&mut self, ev: &InputEvent) -> bool {
+		self.tree.dispatch(ev, &mut self.state)
 	}
 }
 
 impl<T: Component, B: Backend> DynPage<B> for Page<T> {
-	fn draw(&self, surface: &mut B::Surface, bcknd: &mut B, font_state: &mut FontState) {
+	fn draw(&mut self, surface: &mut B::Surface, bcknd: &mut B, font_state: &mut FontState) {
 		self.draw::<B>(surface, bcknd, font_state)
 	}
 
@@ -47,12 +166,62 @@ impl<T: Component, B: Backend> DynPage<B> for Page<T> {
 		self.update_window(win)
 	}
 
+	fn dispatch_input(&mut self, ev: &InputEvent) {
+		self.dispatch_input(ev);
+	}
+
+	fn dispatch_events(&mut self) {
+		self.dispatch_events()
+	}
+
+	fn apply_remote(&mut self) {
+		self.state.apply_remote();
+	}
+
+	fn poll_tasks(&mut self) {
+		self.state.poll_tasks();
+	}
+
+	fn run_timers(&mut self, now: std::time::Instant) {
+		self.state.run_timers(now);
+	}
+
+	fn run_effects(&mut self) {
+		// effects that keep triggering each other continue on the next iteration of the event loop
+		let _ = self.state.run_effects();
+	}
+
+	fn next_deadline(&self) -> Option<std::time::Instant> {
+		self.state.next_deadline()
+	}
+
+	fn has_frame_callbacks(&self) -> bool {
+		self.state.has_frame_callbacks()
+	}
+
 	fn take_redraw_request(&mut self) -> bool {
 		let r = self.state.request_redraw;
 		self.state.request_redraw = false;
 		r
 	}
 
+	fn save_snapshot(&self) {
+		// there is nobody to report this to while the window closes
+		let _ = self.save_snapshot();
+	}
+
+	fn emit_window_resized(&mut self, size: (u32, u32), maximized: bool) {
+		self.state.batch(|state| {
+			state.set(state.window_size, size);
+			state.set(state.window_maximized, maximized);
+
+			// a maximized window is restored to the size it had before
+			if !maximized {
+				state.set(state.window_normal_size, size);
+			}
+		});
+	}
+
 	fn emit_window_moved(&mut self, pos: (i32, i32)) {
 		self.state.set(self.state.window_pos, pos);
 	}
@@ -63,11 +232,359 @@ impl<T: Component, B: Backend> DynPage<B> for Page<T> {
 }
 
 pub(crate) trait DynPage<B: Backend> {
-	fn draw(&self, surface: &mut B::Surface, bcknd: &mut B, font_state: &mut FontState);
+	fn draw(&mut self, surface: &mut B::Surface, bcknd: &mut B, font_state: &mut FontState);
 	fn update_window(&self, win: &mut winit::window::Window);
+	fn dispatch_input(&mut self, ev: &InputEvent);
+	fn dispatch_events(&mut self);
+	fn apply_remote(&mut self);
+	fn poll_tasks(&mut self);
+	fn run_timers(&mut self, now: std::time::Instant);
+	fn run_effects(&mut self);
+
+	fn next_deadline(&self) -> Option<std::time::Instant>;
+	fn has_frame_callbacks(&self) -> bool;
 
 	fn take_redraw_request(&mut self) -> bool;
 
+	fn save_snapshot(&self);
+
+	fn emit_window_resized(&mut self, size: (u32, u32), maximized: bool);
 	fn emit_window_moved(&mut self, pos: (i32, i32));
 	fn emit_window_focus_changed(&mut self, focused: bool);
 }
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::cell::Cell;
+	use std::rc::Rc;
+
+	struct Red;
+
+	impl Component for Red {
+		type Child = ();
+
+		fn build(&self, _: &mut State) -> Self::Child {}
+
+		fn draw<'a, B: Backend>(&self, _: &State, view: &mut B::View<'a>, _: &mut FontState) {
+			view.fill(Color(1.0, 0.0, 0.0, 1.0).into());
+		}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let mut layout = Layout::new();
+			layout.set_margin(Bounds::new(2, 2, 2, 2));
+			layout
+		}
+	}
+
+	#[test]
+	fn test_render_offscreen() {
+		let mut bcknd = unison_backend_soft::SoftBackend::new();
+		let mut font_state = FontState::new();
+
+		let mut page = Page::new(Red);
+		let img = page.render_offscreen(&mut bcknd, (8, 6), &mut font_state).to_image();
+
+		assert_eq!(img.dimensions(), (8, 6));
+		assert_eq!(img.get_pixel(0, 0).0, [0, 0, 0, 255]);
+		assert_eq!(img.get_pixel(2, 2).0, [255, 0, 0, 255]);
+		assert_eq!(img.get_pixel(5, 3).0, [255, 0, 0, 255]);
+		assert_eq!(img.get_pixel(6, 4).0, [0, 0, 0, 255]);
+		assert_eq!(page.state.get(page.state.window_size), Some(&(8, 6)));
+
+		let path = page.hit_test((3.0, 3.0));
+		assert_eq!(path.len(), 1);
+		assert_eq!(path[0].margin_box, Rect::new((0, 0), (8, 6)));
+		assert_eq!(path[0].padding_box, Rect::new((2, 2), (4, 2)));
+
+		assert!(page.hit_test((1.0, 1.0)).is_empty());
+	}
+
+	struct GreenPopup;
+
+	impl Component for GreenPopup {
+		type Child = ();
+
+		fn build(&self, _: &mut State) -> Self::Child {}
+
+		fn draw<'a, B: Backend>(&self, _: &State, view: &mut B::View<'a>, _: &mut FontState) {
+			view.fill(Color(0.0, 1.0, 0.0, 1.0).into());
+		}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let mut layout = Layout::new();
+			layout.set_position(Position::Absolute);
+			layout.set_left(Some(1));
+			layout.set_top(Some(1));
+			layout.set_width(Length::Px(3));
+			layout.set_height(Length::Px(3));
+			layout.set_z_index(Some(1));
+			layout
+		}
+	}
+
+	struct WithPopup;
+
+	impl Component for WithPopup {
+		type Child = (GreenPopup, Red);
+
+		fn build(&self, _: &mut State) -> Self::Child {
+			(GreenPopup, Red)
+		}
+	}
+
+	#[test]
+	fn test_overlay_on_top() {
+		let mut bcknd = unison_backend_soft::SoftBackend::new();
+		let mut font_state = FontState::new();
+
+		let mut page = Page::new(WithPopup);
+		let img = page.render_offscreen(&mut bcknd, (8, 6), &mut font_state).to_image();
+
+		// drawn before, but in a higher layer
+		assert_eq!(img.get_pixel(2, 2).0, [0, 255, 0, 255]);
+		assert_eq!(img.get_pixel(4, 2).0, [255, 0, 0, 255]);
+		assert_eq!(img.get_pixel(1, 1).0, [0, 255, 0, 255]);
+	}
+
+	/// Red at the top, green at the bottom.
+	struct Stripes;
+
+	impl Component for Stripes {
+		type Child = ();
+
+		fn build(&self, _: &mut State) -> Self::Child {}
+
+		fn draw<'a, B: Backend>(&self, _: &State, view: &mut B::View<'a>, _: &mut FontState) {
+			view.draw_rect((0, 0), (8, 10), Color(1.0, 0.0, 0.0, 1.0), None, None);
+			view.draw_rect((0, 10), (8, 10), Color(0.0, 1.0, 0.0, 1.0), None, None);
+		}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let mut layout = Layout::new();
+			layout.set_width(Length::Px(8));
+			layout.set_height(Length::Px(20));
+			layout
+		}
+	}
+
+	/// Leaves some space below the scroll view, sharing its state with the test.
+	/// Rebuilt whenever the window focus changes.
+	struct Scrolling(Rc<Cell<Option<ScrollState>>>);
+
+	impl Component for Scrolling {
+		type Child = ScrollView<Stripes>;
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			state.get(state.window_focused);
+
+			let scroll = self.0.get().unwrap_or_else(|| ScrollState::new(state));
+			self.0.set(Some(scroll));
+
+			ScrollView::new(scroll, |_| Stripes)
+		}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let mut layout = Layout::new();
+			layout.set_padding(Bounds::new(0, 0, 2, 0));
+			layout
+		}
+	}
+
+	#[test]
+	fn test_scroll_view() {
+		let mut bcknd = unison_backend_soft::SoftBackend::new();
+		let mut font_state = FontState::new();
+
+		let shared = Rc::new(Cell::new(None));
+		let mut page = Page::new(Scrolling(shared.clone()));
+		let offset = shared.get().unwrap().offset;
+
+		let mut render = |page: &mut Page<Scrolling>| page.render_offscreen(&mut bcknd, (16, 6), &mut font_state).to_image();
+		let red = [255, 0, 0, 255];
+		let green = [0, 255, 0, 255];
+
+		let img = render(&mut page);
+		assert_eq!(page.state.scroll_extent(offset), (0.0, 16.0));
+		assert_eq!(img.get_pixel(0, 0).0, red);
+		// cut off below the view
+		assert_eq!(img.get_pixel(0, 5).0, [0, 0, 0, 255]);
+		// the scrollbar is drawn on top
+		assert_ne!(img.get_pixel(12, 0).0, [0, 0, 0, 255]);
+
+		assert!(page.dispatch_input(&InputEvent::Scroll { pos: (1.0, 1.0), delta: (0.0, -8.0) }));
+		assert_eq!(page.state.get(offset), Some(&(0.0, 8.0)));
+
+		let img = render(&mut page);
+		assert_eq!(img.get_pixel(0, 1).0, red);
+		assert_eq!(img.get_pixel(0, 2).0, green);
+		assert_eq!(img.get_pixel(0, 5).0, [0, 0, 0, 255]);
+
+		// dragging moves the content along, even if the view is rebuilt in between
+		page.dispatch_input(&InputEvent::PointerPressed { pos: (1.0, 3.0), button: MouseButton::Left });
+		page.state.set(page.state.window_focused, false);
+		render(&mut page);
+		page.dispatch_input(&InputEvent::PointerMoved { pos: (1.0, 1.0) });
+		page.dispatch_input(&InputEvent::PointerReleased { pos: (1.0, 1.0), button: MouseButton::Left });
+		assert_eq!(page.state.get(offset), Some(&(0.0, 10.0)));
+
+		// a release below the view still ends the drag
+		page.dispatch_input(&InputEvent::PointerPressed { pos: (1.0, 3.0), button: MouseButton::Left });
+		page.dispatch_input(&InputEvent::PointerReleased { pos: (1.0, 5.0), button: MouseButton::Left });
+		page.dispatch_input(&InputEvent::PointerMoved { pos: (1.0, 1.0) });
+		assert_eq!(page.state.get(offset), Some(&(0.0, 10.0)));
+
+		let img = render(&mut page);
+		assert_eq!(img.get_pixel(0, 0).0, green);
+
+		// stops at the end of the content
+		page.dispatch_input(&InputEvent::Scroll { pos: (1.0, 1.0), delta: (0.0, -100.0) });
+		assert_eq!(page.state.get(offset), Some(&(0.0, 16.0)));
+		assert!(!page.dispatch_input(&InputEvent::Scroll { pos: (1.0, 1.0), delta: (0.0, -1.0) }));
+
+		// the content stays in view when the offset is set out of range
+		page.state.set(offset, (0.0, 100.0));
+		let img = render(&mut page);
+		assert_eq!(img.get_pixel(0, 3).0, green);
+		assert_eq!(img.get_pixel(0, 5).0, [0, 0, 0, 255]);
+
+		// reading the extent is tracked, a larger view can be scrolled less
+		let reader = ContainerId::new();
+		page.state.track(reader, Phase::Draw, || page.state.scroll_extent(offset));
+
+		page.render_offscreen(&mut bcknd, (16, 10), &mut font_state);
+		assert_eq!(page.state.scroll_extent(offset), (0.0, 12.0));
+		assert_eq!(page.state.is_dirty(reader), Some(Dirty::Redraw));
+	}
+
+	/// Cuts off its content, which contains a popup.
+	struct Clipped;
+
+	impl Component for Clipped {
+		type Child = (Stripes, GreenPopup);
+
+		fn build(&self, _: &mut State) -> Self::Child {
+			(Stripes, GreenPopup)
+		}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let mut layout = Layout::new();
+			layout.set_width(Length::Px(2));
+			layout.set_height(Length::Px(2));
+			layout.set_overflow(Overflow::Clip);
+			layout
+		}
+	}
+
+	#[test]
+	fn test_overlay_not_clipped() {
+		let mut bcknd = unison_backend_soft::SoftBackend::new();
+		let mut font_state = FontState::new();
+
+		let mut page = Page::new(Clipped);
+		let img = page.render_offscreen(&mut bcknd, (8, 6), &mut font_state).to_image();
+
+		// the content within the same layer is cut off, the popup in the layer above is not
+		assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+		assert_eq!(img.get_pixel(0, 3).0, [0, 0, 0, 255]);
+		assert_eq!(img.get_pixel(3, 3).0, [0, 255, 0, 255]);
+
+		let path = page.hit_test((3.0, 3.0));
+		assert_eq!(path.len(), 1);
+		assert_eq!(path[0].padding_box, Rect::new((1, 1), (3, 3)));
+
+		assert!(page.hit_test((0.0, 3.0)).is_empty());
+	}
+
+	/// Only has a margin while the window is focused.
+	struct FocusMargin;
+
+	impl Component for FocusMargin {
+		type Child = ();
+
+		fn build(&self, _: &mut State) -> Self::Child {}
+
+		fn layout(&self, state: &mut State) -> Layout {
+			let focused = state.window_focused;
+
+			let mut layout = Layout::new();
+			layout.set_margin(bind!(focused => if focused { Bounds::new(2, 2, 2, 2) } else { Bounds::default() }));
+			layout
+		}
+	}
+
+	#[test]
+	fn test_relayout_on_change() {
+		let mut bcknd = unison_backend_soft::SoftBackend::new();
+		let mut font_state = FontState::new();
+
+		let mut page = Page::new(FocusMargin);
+		page.render_offscreen(&mut bcknd, (8, 6), &mut font_state);
+
+		assert!(!page.state.request_redraw);
+		assert_eq!(page.hit_test((3.0, 3.0))[0].padding_box, Rect::new((2, 2), (4, 2)));
+
+		// unrelated values do not cause a redraw
+		page.state.set(page.state.window_pos, (10, 10));
+		assert!(!page.state.request_redraw);
+
+		page.state.set(page.state.window_focused, false);
+		assert!(page.state.request_redraw);
+
+		page.render_offscreen(&mut bcknd, (8, 6), &mut font_state);
+		assert_eq!(page.hit_test((3.0, 3.0))[0].padding_box, Rect::new((0, 0), (8, 6)));
+	}
+
+	/// Restores its tab while being built.
+	struct Tabs;
+
+	impl Component for Tabs {
+		type Child = ();
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			let tab = state.alloc(0u32);
+			state.persist("tab", tab).unwrap();
+
+			// the persisted value is available right away
+			let restored = *state.get(tab).unwrap();
+			state.set(state.window_focused, restored == 3);
+		}
+	}
+
+	#[test]
+	fn test_persistent_page() {
+		let path = std::env::temp_dir().join(format!("unison-test-{}.json", std::process::id()));
+		// the broken position does not keep the size from being restored
+		std::fs::write(&path, r#"{ "tab": 3, "window_size": [640, 480], "window_pos": "left" }"#).unwrap();
+
+		let mut page = Page::new_persistent(Tabs, &path);
+
+		assert!(page.state.is_restored("window_size"));
+		assert!(!page.state.is_restored("window_pos"));
+		assert_eq!(page.state.get(page.state.window_focused), Some(&true));
+		assert_eq!(page.state.get(page.state.window_normal_size), Some(&(640, 480)));
+
+		// the size while maximized is not saved
+		DynPage::<unison_backend_soft::SoftBackend>::emit_window_resized(&mut page, (800, 600), false);
+		DynPage::<unison_backend_soft::SoftBackend>::emit_window_resized(&mut page, (1920, 1080), true);
+
+		page.save_snapshot().unwrap();
+		let saved = std::fs::read_to_string(&path).unwrap();
+		std::fs::remove_file(&path).unwrap();
+
+		let mut restored = State::new();
+		restored.persist_window().unwrap();
+		restored.restore_snapshot(&saved).unwrap();
+
+		assert_eq!(restored.get(restored.window_normal_size), Some(&(800, 600)));
+		assert_eq!(restored.get(restored.window_maximized), Some(&true));
+		assert!(saved.contains("\"tab\": 3"));
+
+		// a missing snapshot starts from scratch
+		let page = Page::new_persistent(Tabs, &path);
+		assert!(!page.state.is_restored("window_size"));
+		assert_eq!(page.state.get(page.state.window_focused), Some(&false));
+	}
+}
@@ -0,0 +1,227 @@
+//! Persisting values between runs.
+//!
+//! Values registered using [State::persist] are saved into a JSON snapshot under their name.
+//! When a snapshot is restored, the values of registered refs are replaced right away,
+//! values that are registered later (e.g. while a component is built) are restored on registration.
+
+use crate::*;
+
+use std::collections::{ HashMap, HashSet };
+use std::path::Path;
+
+use serde::{ Serialize, de::DeserializeOwned };
+
+
+#[derive(Debug, thiserror::Error)]
+pub enum PersistError {
+	#[error("failed to read or write snapshot: {0}")]
+	Io(#[from] std::io::Error),
+	#[error("invalid snapshot: {0}")]
+	Format(#[from] serde_json::Error),
+}
+
+
+type SaveFn = Box<dyn Fn(&State) -> Option<serde_json::Value>>;
+type RestoreFn = Box<dyn Fn(&mut State, serde_json::Value) -> Result<(), serde_json::Error>>;
+
+struct Persistent {
+	save: SaveFn,
+	restore: RestoreFn,
+}
+
+#[derive(Default)]
+pub(crate) struct Persistence {
+	entries: HashMap<String, Persistent>,
+	/// Restored values, whose refs have not been registered yet.
+	pending: HashMap<String, serde_json::Value>,
+	/// The names of the values that have been restored successfully.
+	restored: HashSet<String>,
+}
+
+
+impl State {
+	/// Persist the value behind `r` under `name`.
+	/// 
+	/// If a restored snapshot contains a value for `name`, it replaces the current value.
+	/// 
+	/// Example:
+	/// ```rust
+	/// use unison::*;
+	/// 
+	/// let mut state = State::new();
+	/// state.restore_snapshot(r#"{ "selected_tab": 2 }"#).unwrap();
+	/// 
+	/// let tab = state.alloc(0);
+	/// state.persist("selected_tab", tab).unwrap();
+	/// 
+	/// assert_eq!(state.get(tab), Some(&2));
+	/// ```
+	pub fn persist<T>(&mut self, name: &str, r: arena::Ref<T>) -> Result<(), PersistError> where
+		T: Serialize + DeserializeOwned + PartialEq + 'static,
+	{
+		let entry = Persistent {
+			save: Box::new(move |state| state.arena.get(r).and_then(|v| serde_json::to_value(v).ok())),
+			restore: Box::new(move |state, val| {
+				state.set(r, serde_json::from_value(val)?);
+				Ok(())
+			}),
+		};
+
+		let result = match self.persistence.pending.remove(name) {
+			Some(val) => self.restore_value(name, &entry, val).map_err(PersistError::from),
+			None => Ok(()),
+		};
+
+		self.persistence.entries.insert(name.to_owned(), entry);
+
+		result
+	}
+
+	/// Persist the size, position and maximized state of the window.
+	/// 
+	/// The size is the one the window had before it was maximized.
+	pub fn persist_window(&mut self) -> Result<(), PersistError> {
+		self.persist("window_size", self.window_normal_size)?;
+		self.persist("window_pos", self.window_pos)?;
+		self.persist("window_maximized", self.window_maximized)
+	}
+
+	/// Check whether the value registered under `name` has been restored from a snapshot.
+	pub fn is_restored(&self, name: &str) -> bool {
+		self.persistence.restored.contains(name)
+	}
+
+	/// Stop persisting the value registered under `name`.
+	pub fn forget_persistent(&mut self, name: &str) {
+		self.persistence.entries.remove(name);
+	}
+
+	/// Serialize all persisted values into a JSON snapshot.
+	/// 
+	/// Restored values that have not been registered again are kept.
+	pub fn snapshot(&self) -> Result<String, PersistError> {
+		let mut values: serde_json::Map<String, serde_json::Value> = self.persistence.pending.iter()
+			.map(|(name, val)| (name.clone(), val.clone()))
+			.collect();
+
+		for (name, entry) in &self.persistence.entries {
+			// values that are not alive anymore are dropped
+			if let Some(val) = (entry.save)(self) {
+				values.insert(name.clone(), val);
+			}
+		}
+
+		Ok(serde_json::to_string_pretty(&values)?)
+	}
+
+	/// Restore the values from a JSON snapshot.
+	/// 
+	/// Values that cannot be deserialized are skipped, the first error is returned after all other values have been restored.
+	pub fn restore_snapshot(&mut self, json: &str) -> Result<(), PersistError> {
+		let values: HashMap<String, serde_json::Value> = serde_json::from_str(json)?;
+
+		// the restore functions need the State mutably
+		let entries = std::mem::take(&mut self.persistence.entries);
+		let mut result = Ok(());
+
+		self.batch(|state| {
+			for (name, val) in values {
+				match entries.get(&name) {
+					Some(entry) => if let Err(e) = state.restore_value(&name, entry, val) {
+						if result.is_ok() {
+							result = Err(e.into());
+						}
+					},
+					None => {
+						state.persistence.pending.insert(name, val);
+					},
+				}
+			}
+		});
+
+		self.persistence.entries.extend(entries);
+
+		result
+	}
+
+	/// Restore `val` using `entry`, remembering that `name` has been restored.
+	fn restore_value(&mut self, name: &str, entry: &Persistent, val: serde_json::Value) -> Result<(), serde_json::Error> {
+		(entry.restore)(self, val)?;
+		self.persistence.restored.insert(name.to_owned());
+		Ok(())
+	}
+
+	/// Write a snapshot of all persisted values to `path`, see [State::snapshot].
+	pub fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<(), PersistError> {
+		std::fs::write(path, self.snapshot()?)?;
+		Ok(())
+	}
+
+	/// Restore a snapshot from `path`, see [State::restore_snapshot].
+	pub fn load_snapshot(&mut self, path: impl AsRef<Path>) -> Result<(), PersistError> {
+		let json = std::fs::read_to_string(path)?;
+		self.restore_snapshot(&json)
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_snapshot() {
+		let mut state = State::new();
+
+		let name = state.alloc(String::from("unison"));
+		let tabs = state.alloc(vec![1, 2]);
+
+		state.persist_window().unwrap();
+		state.persist("name", name).unwrap();
+		state.persist("tabs", tabs).unwrap();
+
+		state.set(state.window_normal_size, (800, 600));
+		state.set(state.window_maximized, true);
+
+		let snapshot = state.snapshot().unwrap();
+
+		let mut restored = State::new();
+		restored.persist_window().unwrap();
+		restored.restore_snapshot(&snapshot).unwrap();
+
+		assert_eq!(restored.get(restored.window_normal_size), Some(&(800, 600)));
+		assert!(restored.is_restored("window_size"));
+		assert_eq!(restored.get(restored.window_maximized), Some(&true));
+
+		// registered after the snapshot has been restored
+		let tabs = restored.alloc(Vec::new());
+		restored.persist("tabs", tabs).unwrap();
+		assert_eq!(restored.get(tabs), Some(&vec![1, 2]));
+
+		// values that have not been registered yet are kept
+		let snapshot = restored.snapshot().unwrap();
+		assert!(snapshot.contains("unison"));
+	}
+
+	#[test]
+	fn test_invalid_snapshot() {
+		let mut state = State::new();
+
+		let a = state.alloc(0u32);
+		let b = state.alloc(0u32);
+
+		state.persist("a", a).unwrap();
+		state.persist("b", b).unwrap();
+
+		assert!(matches!(state.restore_snapshot("not json"), Err(PersistError::Format(_))));
+
+		// the valid value is still restored
+		assert!(state.restore_snapshot(r#"{ "a": "text", "b": 3 }"#).is_err());
+		assert_eq!(state.get(a), Some(&0));
+		assert_eq!(state.get(b), Some(&3));
+		assert!(!state.is_restored("a") && state.is_restored("b"));
+
+		state.free(b);
+		assert!(!state.snapshot().unwrap().contains("\"b\""));
+	}
+}
@@ -1,4 +1,9 @@
 use crate::*;
+use crate::arena::RefId;
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{ HashMap, HashSet };
 
 
 /// Any value that can be lazily evaluated given a [State]
@@ -12,11 +17,19 @@ pub trait LazyValue {
 
 	/// Unsafely evaluate without [State], bypassing all safety checks.
 	/// 
+	/// # Safety
+	/// 
 	/// Only use when:
 	/// - You are sure that all used [arena::Ref]s are alive:
 	/// - The [State] (s) has not been cleared in any way.
 	/// - There is no exclusive reference to the [State] (s).
 	unsafe fn eval_unchecked(&self) -> Self::Output;
+
+	/// Get the state of a running animation, see [Animated].
+	fn animation(&self) -> Option<&dyn Any> { None }
+
+	/// Continue the running animation of `old`, which this value replaces.
+	fn continue_from(&self, _old: &dyn LazyValue<Output = Self::Output>) {}
 }
 
 /// A lazily evaluated binding.
@@ -122,15 +135,157 @@ macro_rules! bind {
 }
 
 
-impl<T: Copy> LazyValue for arena::Ref<T> {
+/// A [LazyValue] that caches its output until one of the values it read changes.
+/// 
+/// Prefer using the [computed!] macro.
+/// 
+/// Example:
+/// ```rust
+/// use unison::*;
+/// 
+/// let mut state = State::new();
+/// 
+/// let rows = state.alloc(vec![1, 2, 3]);
+/// let sum = computed!(rows => rows.iter().sum::<i32>());
+/// 
+/// assert_eq!(sum.eval(&state), Some(6)); // evaluated
+/// assert_eq!(sum.eval(&state), Some(6)); // cached
+/// 
+/// state.modify(rows, |r| r.push(4));
+/// assert_eq!(sum.eval(&state), Some(10)); // evaluated again
+/// ```
+pub struct Computed<T> {
+	value: Box<dyn LazyValue<Output = T>>,
+	cache: RefCell<Option<ComputedCache<T>>>,
+}
+
+/// The last output and the versions of the values read to compute it.
+type ComputedCache<T> = (Option<T>, Vec<(RefId, Option<u64>)>);
+
+impl<T: Clone> Computed<T> {
+	pub fn new<V: LazyValue<Output = T> + 'static>(value: V) -> Self {
+		Self {
+			value: Box::new(value),
+			cache: RefCell::new(None),
+		}
+	}
+
+	/// Drop the cached output, so that the next evaluation computes it again.
+	pub fn invalidate(&self) {
+		self.cache.borrow_mut().take();
+	}
+}
+
+impl<T: Clone> LazyValue for Computed<T> {
+	type Output = T;
+
+	fn eval(&self, state: &State) -> Option<Self::Output> {
+		if let Some((val, reads)) = &*self.cache.borrow() {
+			if reads.iter().all(|(id, version)| state.version_of(*id) == *version) {
+				// the cached output still depends on these values
+				for (id, _) in reads {
+					state.record_read(*id);
+				}
+
+				return val.clone();
+			}
+		}
+
+		let (val, reads) = state.capture_reads(|| self.value.eval(state));
+
+		let reads = reads.into_iter()
+			.map(|id| (id, state.version_of(id)))
+			.collect();
+
+		*self.cache.borrow_mut() = Some((val.clone(), reads));
+
+		val
+	}
+
+	unsafe fn eval_unchecked(&self) -> Self::Output {
+		self.value.eval_unchecked()
+	}
+}
+
+impl<T: Copy + 'static> IntoValue for Computed<T> {
+	type Output = T;
+
+	fn into_value(self) -> Value<Self::Output> {
+		Value::Lazy(Box::new(self))
+	}
+}
+
+/// Create a [Computed] value from a [Binding].
+/// 
+/// Uses the same syntax as [bind!].
+#[macro_export]
+macro_rules! computed {
+	( $( $name:ident ),* => $e:expr ) => {
+		{
+			Computed::new(bind!( $( $name ),* => $e ))
+		}
+	};
+}
+
+
+impl<T: Clone> LazyValue for arena::Ref<T> {
 	type Output = T;
 
 	fn eval(&self, state: &State) -> Option<Self::Output> {
-		state.arena.get(*self).map(|v|*v)
+		state.get(*self).cloned()
 	}
 
 	unsafe fn eval_unchecked(&self) -> Self::Output {
-		*self.get_unchecked()
+		self.get_unchecked().clone()
+	}
+}
+
+impl<T> arena::Ref<T> {
+	/// Create a [LazyValue] that passes the value to `func` by reference instead of cloning it.
+	/// 
+	/// Example:
+	/// ```rust
+	/// use unison::*;
+	/// 
+	/// let mut state = State::new();
+	/// 
+	/// let title = state.alloc(String::from("unison"));
+	/// let len = title.map(|t| t.len());
+	/// 
+	/// assert_eq!(len.eval(&state), Some(6));
+	/// ```
+	pub fn map<F, O>(self, func: F) -> RefMap<T, F> where F: Fn(&T) -> O {
+		RefMap { r: self, func }
+	}
+}
+
+/// A [LazyValue] that reads a value by reference, see [arena::Ref::map].
+pub struct RefMap<T, F> {
+	r: arena::Ref<T>,
+	func: F,
+}
+
+impl<T, F, O> LazyValue for RefMap<T, F> where F: Fn(&T) -> O {
+	type Output = O;
+
+	fn eval(&self, state: &State) -> Option<Self::Output> {
+		state.get(self.r).map(&self.func)
+	}
+
+	unsafe fn eval_unchecked(&self) -> Self::Output {
+		(self.func)(self.r.get_unchecked())
+	}
+}
+
+impl<T, F, O> IntoValue for RefMap<T, F> where
+	T: 'static,
+	F: Fn(&T) -> O + 'static,
+	O: Copy + 'static,
+{
+	type Output = O;
+
+	fn into_value(self) -> Value<Self::Output> {
+		Value::Lazy(Box::new(self))
 	}
 }
 
@@ -141,9 +296,7 @@ impl LazyValue for () {
 		Some(())
 	}
 
-	unsafe fn eval_unchecked(&self) -> Self::Output {
-		()
-	}
+	unsafe fn eval_unchecked(&self) -> Self::Output {}
 }
 
 macro_rules! impl_tuple_lazy {
@@ -197,6 +350,13 @@ impl<T: Copy> Value<T> {
 			Self::Lazy(v) => v.eval(state),
 		}
 	}
+
+	/// Continue the running animation of `old`, which this value replaces.
+	pub(crate) fn continue_from(&self, old: &Value<T>) {
+		if let (Self::Lazy(new), Self::Lazy(old)) = (self, old) {
+			new.continue_from(old.as_ref());
+		}
+	}
 }
 
 pub trait IntoValue {
@@ -234,6 +394,152 @@ impl<T: Copy> IntoValue for Box<dyn LazyValue<Output = T>> {
 }
 
 
+/// Identifies an effect created using [State::effect].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EffectId(ContainerId);
+
+type EffectFn = Box<dyn FnMut(&mut State)>;
+
+struct Effect {
+	/// Evaluates the inputs and runs the effect, taken out while running.
+	run: Option<EffectFn>,
+	owner: Option<ContainerId>,
+}
+
+/// How often [State::run_effects] runs the pending effects before giving up.
+const MAX_EFFECT_ROUNDS: usize = 100;
+
+/// Returned by [State::run_effects] when effects keep triggering themselves or each other.
+#[derive(Debug, thiserror::Error)]
+#[error("{pending} effects are still pending after {MAX_EFFECT_ROUNDS} rounds, they might trigger each other")]
+pub struct EffectLoopError {
+	/// The number of effects that are still pending.
+	pub pending: usize,
+}
+
+/// Effects are tracked like containers, their inputs are recorded under [Phase::Build].
+#[derive(Default)]
+pub(crate) struct Effects {
+	effects: HashMap<ContainerId, Effect>,
+	pending: HashSet<ContainerId>,
+}
+
+impl Effects {
+	pub fn contains(&self, id: ContainerId) -> bool {
+		self.effects.contains_key(&id)
+	}
+
+	pub fn mark_pending(&mut self, id: ContainerId) {
+		self.pending.insert(id);
+	}
+}
+
+impl State {
+	/// Run `f` with the value of `inputs` now and whenever any value read by `inputs` changes.
+	/// 
+	/// Pending effects are run by the app after each batch of events, see [State::run_effects].
+	/// Values read within `f` itself are not tracked.
+	/// 
+	/// Example:
+	/// ```rust
+	/// use unison::*;
+	/// 
+	/// let mut state = State::new();
+	/// 
+	/// let count = state.alloc(0);
+	/// let log = state.alloc(Vec::new());
+	/// 
+	/// state.effect(count, move |count, state| { state.modify(log, |l| l.push(count)); });
+	/// 
+	/// state.set(count, 1);
+	/// state.run_effects().unwrap();
+	/// 
+	/// assert_eq!(state.get(log).unwrap(), &[0, 1]);
+	/// ```
+	pub fn effect<I, F>(&mut self, inputs: I, mut f: F) -> EffectId where
+		I: LazyValue + 'static,
+		F: FnMut(I::Output, &mut State) + 'static,
+	{
+		let id = ContainerId::new();
+
+		let run = Box::new(move |state: &mut State| {
+			if let Some(val) = state.track(id, Phase::Build, || inputs.eval(state)) {
+				f(val, state);
+			}
+		});
+
+		let owner = self.current_owner();
+		self.effects.effects.insert(id, Effect { run: Some(run), owner });
+		self.run_effect(id);
+
+		EffectId(id)
+	}
+
+	/// Remove an effect, it will not run again.
+	/// 
+	/// Returns `false` if the effect has already been removed.
+	pub fn remove_effect(&mut self, id: EffectId) -> bool {
+		self.dependencies.borrow_mut().remove(id.0);
+		self.effects.pending.remove(&id.0);
+		self.effects.effects.remove(&id.0).is_some()
+	}
+
+	/// Run all effects whose inputs have changed, until no more effects are pending.
+	/// 
+	/// Effects that keep triggering themselves or each other are stopped after a fixed number of rounds,
+	/// returning an error. The effects that are still pending run on the next call.
+	pub fn run_effects(&mut self) -> Result<(), EffectLoopError> {
+		for _ in 0..MAX_EFFECT_ROUNDS {
+			if self.effects.pending.is_empty() {
+				return Ok(());
+			}
+
+			for id in std::mem::take(&mut self.effects.pending) {
+				self.run_effect(id);
+			}
+		}
+
+		match self.effects.pending.len() {
+			0 => Ok(()),
+			pending => Err(EffectLoopError { pending }),
+		}
+	}
+
+	fn run_effect(&mut self, id: ContainerId) {
+		// the effect is missing if it was removed, and has no `run` while it is already running
+		let (mut run, owner) = match self.effects.effects.get_mut(&id) {
+			Some(Effect { run, owner }) => match run.take() {
+				Some(run) => (run, *owner),
+				None => return,
+			},
+			None => return,
+		};
+
+		match owner {
+			Some(owner) => self.with_owner(owner, |state| run(state)),
+			None => run(self),
+		}
+
+		// put it back, unless the effect removed itself
+		if let Some(e) = self.effects.effects.get_mut(&id) {
+			e.run = Some(run);
+		}
+	}
+
+	/// Remove all effects that belong to the container `id`.
+	pub(crate) fn remove_owned_effects(&mut self, id: ContainerId) {
+		let owned: Vec<ContainerId> = self.effects.effects.iter()
+			.filter(|(_, e)| e.owner == Some(id))
+			.map(|(id, _)| *id)
+			.collect();
+
+		for id in owned {
+			self.remove_effect(EffectId(id));
+		}
+	}
+}
+
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -292,5 +598,153 @@ mod tests {
 			let _ = bind!( => 12); // i mean, why not
 		}
 	}
-}
 
+	#[test]
+	fn test_non_copy_values() {
+		let mut state = State::new();
+
+		let title = state.alloc(String::from("unison"));
+		let rows = state.alloc(vec![1, 2, 3]);
+
+		let greeting = bind!(title => format!("hello {}", title));
+		let sum = rows.map(|r| r.iter().sum::<i32>());
+
+		assert_eq!(greeting.eval(&state).unwrap(), "hello unison");
+		assert_eq!(sum.eval(&state), Some(6));
+
+		// setting an equal value is not a change
+		state.set(title, String::from("unison"));
+		assert_eq!(state.version(title), 0);
+
+		state.mutate_ref(title, |t| t.push('!'));
+		assert_eq!(state.version(title), 1);
+		assert_eq!(greeting.eval(&state).unwrap(), "hello unison!");
+
+		// values without PartialEq always count as changed
+		struct Model(Vec<i32>);
+
+		let model = state.alloc(Model(Vec::new()));
+		state.modify(model, |m| m.0.push(4));
+		state.modify(rows, |r| r.push(4));
+
+		assert_eq!(state.version(model), 1);
+		assert_eq!(state.version(rows), 1);
+		assert_eq!(sum.eval(&state), Some(10));
+	}
+
+	#[test]
+	fn test_computed() {
+		use std::cell::Cell;
+		use std::rc::Rc;
+
+		let mut state = State::new();
+
+		let a = state.alloc(1);
+		let b = state.alloc(2);
+		let unrelated = state.alloc(0);
+
+		let calls = Rc::new(Cell::new(0));
+		let c = calls.clone();
+
+		let sum = computed!(a, b => { c.set(c.get() + 1); a + b });
+		let doubled = Computed::new(Binding::new(sum, |s| s * 2));
+
+		assert_eq!(doubled.eval(&state), Some(6));
+		assert_eq!(doubled.eval(&state), Some(6));
+		assert_eq!(calls.get(), 1);
+
+		state.set(unrelated, 1);
+		assert_eq!(doubled.eval(&state), Some(6));
+		assert_eq!(calls.get(), 1);
+
+		state.set(b, 3);
+		assert_eq!(doubled.eval(&state), Some(8));
+		assert_eq!(calls.get(), 2);
+
+		// cached reads are still recorded as dependencies
+		let id = ContainerId::new();
+		state.track(id, Phase::Layout, || doubled.eval(&state));
+
+		state.set(a, 2);
+		assert_eq!(state.is_dirty(id), Some(Dirty::Layout));
+
+		state.free(a);
+		assert_eq!(doubled.eval(&state), None);
+	}
+
+	#[test]
+	fn test_effects() {
+		let mut state = State::new();
+
+		let a = state.alloc(1);
+		let b = state.alloc(1);
+		let sum = state.alloc(0);
+		let runs = state.alloc(0);
+
+		// effects can trigger other effects
+		let effect = state.effect(bind!(a, b => a + b), move |s, state| {
+			state.set(sum, s);
+			state.mutate_ref(runs, |r| *r += 1);
+		});
+		state.effect(sum, move |s, state| { state.set(b, s.min(10)); });
+
+		assert_eq!(state.get(sum), Some(&2));
+
+		state.set(a, 5);
+		state.run_effects().unwrap();
+
+		// 5 + 1 -> 5 + 6 -> 5 + 10
+		assert_eq!(state.get(sum), Some(&15));
+		assert_eq!(state.get(b), Some(&10));
+
+		// nothing changed, nothing to run
+		let count = *state.get(runs).unwrap();
+		state.run_effects().unwrap();
+		assert_eq!(state.get(runs), Some(&count));
+
+		assert!(state.remove_effect(effect));
+		state.set(a, 0);
+		state.run_effects().unwrap();
+
+		assert_eq!(state.get(runs), Some(&count));
+		assert!(!state.remove_effect(effect));
+
+		// effects do not make the page redraw
+		assert!(state.take_dirty().is_empty());
+	}
+
+	#[test]
+	fn test_effect_loop() {
+		let mut state = State::new();
+
+		let count = state.alloc(0);
+		let effect = state.effect(count, move |c, state| { state.set(count, c + 1); });
+
+		// the effect keeps triggering itself, but does not hang
+		assert_eq!(state.run_effects().unwrap_err().pending, 1);
+		assert_eq!(state.get(count), Some(&(MAX_EFFECT_ROUNDS + 1)));
+
+		// it continues on the next call, until it is removed
+		assert!(state.run_effects().is_err());
+		assert_eq!(state.get(count), Some(&(2 * MAX_EFFECT_ROUNDS + 1)));
+
+		state.remove_effect(effect);
+		assert!(state.run_effects().is_ok());
+	}
+
+	#[test]
+	fn test_reentrant_effects() {
+		let mut state = State::new();
+
+		let count = state.alloc(0);
+
+		// the effect is pending again while it runs, the nested call skips it instead of panicking
+		state.effect(count, move |c, state| {
+			state.set(count, c + 1);
+			state.run_effects().unwrap();
+		});
+
+		assert_eq!(state.get(count), Some(&1));
+		assert!(state.run_effects().is_ok());
+	}
+}
@@ -2,11 +2,30 @@ use crate::*;
 
 use std::collections::HashMap;
 
-use winit::event_loop::{ EventLoop, EventLoopWindowTarget };
+use winit::event_loop::{ ControlFlow, EventLoop, EventLoopBuilder, EventLoopProxy, EventLoopWindowTarget };
 use winit::window::{ WindowId, Window };
 use winit::event::{ Event, WindowEvent };
 
 
+/// Events sent to the event loop from other threads.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum UserEvent {
+	/// A [StateHandle] has queued an operation.
+	Wake,
+}
+
+/// The proxy of the running event loop, there can only be one per process.
+static EVENT_LOOP_PROXY: std::sync::Mutex<Option<EventLoopProxy<UserEvent>>> = std::sync::Mutex::new(None);
+
+/// Wake up the event loop, if it is running.
+pub(crate) fn wake_event_loop() {
+	if let Some(proxy) = &*EVENT_LOOP_PROXY.lock().unwrap() {
+		// fails only if the event loop is gone
+		let _ = proxy.send_event(UserEvent::Wake);
+	}
+}
+
+
 pub struct App<B: Backend + 'static = unison_backend_wgpu::WgpuBackend> {
 	viewports: HashMap<WindowId, Viewport<B>>,
 	window_queue: Vec<Box<dyn DynPage<B>>>,
@@ -25,6 +44,12 @@ impl App<unison_backend_wgpu::WgpuBackend> {
 	}
 }
 
+impl Default for App<unison_backend_wgpu::WgpuBackend> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl<B: Backend + 'static> App<B> {
 	pub fn with_backend(backend: B) -> Self {
 		Self {
@@ -46,16 +71,28 @@ impl<B: Backend + 'static> App<B> {
 			None => return,
 		};
 
+		if let Some(input) = vp.input.translate(&ev) {
+			vp.page.dispatch_input(&input);
+		}
+
 		match ev {
 			WindowEvent::Resized(size) => {
 				vp.reconfigure(&self.backend, (size.width.max(1), size.height.max(1)));
+
+				let maximized = vp.get_window().is_maximized();
+				vp.page.emit_window_resized((size.width, size.height), maximized);
 			},
 			WindowEvent::Moved(p) => {
 				vp.page.emit_window_moved((p.x, p.y));
 			},
 			WindowEvent::Focused(f) => {
 				vp.page.emit_window_focus_changed(f);
-			}
+			},
+			WindowEvent::CloseRequested => {
+				vp.page.save_snapshot();
+				self.viewports.remove(&id);
+				return;
+			},
 			_ => {}
 		}
 
@@ -64,23 +101,62 @@ impl<B: Backend + 'static> App<B> {
 		}
 	}
 
+	fn handle_main_events_cleared(&mut self) {
+		let now = std::time::Instant::now();
+
+		for vp in self.viewports.values_mut() {
+			vp.page.apply_remote();
+			vp.page.poll_tasks();
+			vp.page.run_timers(now);
+			vp.page.dispatch_events();
+			vp.page.run_effects();
+
+			if vp.page.take_redraw_request() {
+				vp.get_window().request_redraw();
+			}
+		}
+	}
+
 	fn handle_redraw(&mut self, id: WindowId) {
 		if let Some(v) = self.viewports.get_mut(&id) {
 			v.draw(&mut self.backend, &mut self.font_state);
 		}
 	}
 
+	/// Poll while animating, otherwise sleep until the next timer is due or an event arrives.
+	fn control_flow(&self) -> ControlFlow {
+		// the last window has been closed
+		if self.viewports.is_empty() {
+			return ControlFlow::Exit;
+		}
+
+		if self.viewports.values().any(|vp| vp.page.has_frame_callbacks()) {
+			return ControlFlow::Poll;
+		}
+
+		match self.viewports.values().filter_map(|vp| vp.page.next_deadline()).min() {
+			Some(deadline) => ControlFlow::WaitUntil(deadline),
+			None => ControlFlow::Wait,
+		}
+	}
+
+	/// Open all queued windows and run the event loop.
+	/// 
+	/// Closing a window saves its page's snapshot and removes it, the app exits once the last window has been closed.
 	pub fn run(mut self) -> ! {
-		let ev_loop = EventLoop::new();
+		let ev_loop: EventLoop<UserEvent> = EventLoopBuilder::with_user_event().build();
+		*EVENT_LOOP_PROXY.lock().unwrap() = Some(ev_loop.create_proxy());
 
 		self.viewports = self.window_queue.drain(..)
 			.map(|page| Viewport::new(&ev_loop, &self.backend, page).unwrap()) // TODO: get rid of unwrap
 			.collect();
 
-		ev_loop.run(move |ev, _, _cf| {
+		ev_loop.run(move |ev, _, cf| {
 			match ev {
 				Event::WindowEvent { window_id, event } => self.handle_window_event(window_id, event),
+				Event::MainEventsCleared => self.handle_main_events_cleared(),
 				Event::RedrawRequested(id) => self.handle_redraw(id),
+				Event::RedrawEventsCleared => *cf = self.control_flow(),
 				_ => {}
 			}
 		});
@@ -88,8 +164,10 @@ impl<B: Backend + 'static> App<B> {
 }
 
 struct Viewport<B: Backend> {
-	window: Window,
+	// the surface has to be dropped before the window it was created for
 	surface: B::Surface,
+	window: Window,
+	input: InputTranslator,
 	pub(crate) page: Box<dyn DynPage<B>>,
 }
 
@@ -106,6 +184,7 @@ impl<B: Backend> Viewport<B> {
 		Ok((window.id(), Viewport {
 			window,
 			surface,
+			input: InputTranslator::default(),
 			page,
 		}))
 	}
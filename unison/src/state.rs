@@ -1,7 +1,10 @@
 use crate::*;
+use crate::handle::RemoteOp;
 use crate::arena::Ref;
 
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 
 
 pub struct State {
@@ -13,12 +16,73 @@ pub struct State {
 	pub window_focused: Ref<bool>,
 	pub window_maximized: Ref<bool>,
 	pub window_minimized: Ref<bool>,
+	/// The size of the window while it is not maximized, which is persisted instead of `window_size`.
+	pub(crate) window_normal_size: Ref<(u32, u32)>,
 
-	redraw_refs: HashSet<(usize, usize)>,
+	pub(crate) dependencies: RefCell<tracking::Dependencies>,
+	pub(crate) dirty: HashMap<ContainerId, Dirty>,
+	/// Containers that have been removed since the last call to [State::take_unmounted].
+	unmounted: Vec<ContainerId>,
+	/// How often each value has changed, see [State::version].
+	versions: HashMap<arena::RefId, u64>,
+	pub(crate) task_state: tasks::TaskState,
+	pub(crate) timer_state: timers::TimerState,
+	pub(crate) effects: reactivity::Effects,
+	pub(crate) history: history::History,
+	pub(crate) persistence: persist::Persistence,
+	pub(crate) contexts: RefCell<context::Contexts>,
+	/// See [State::scroll_extent], a value so that reading it is tracked.
+	pub(crate) scroll_extents: Ref<HashMap<arena::RefId, (f32, f32)>>,
+	/// Operations queued by [StateHandle]s.
+	pub(crate) remote: (std::sync::mpsc::Sender<RemoteOp>, std::sync::mpsc::Receiver<RemoteOp>),
+
+	/// Changes deferred by [State::batch].
+	batch: Option<Batch>,
 
 	pub(crate) request_redraw: bool,
 }
 
+
+/// Checks whether a value differs from the value it had before the batch.
+type ChangedFn = Box<dyn Fn(&State) -> bool>;
+
+#[derive(Default)]
+struct Batch {
+	/// How many calls to [State::batch] are running.
+	depth: usize,
+	/// The changed values, in the order of their first change.
+	/// Values that cannot be compared are always considered changed.
+	changes: Vec<(arena::RefId, Option<ChangedFn>)>,
+}
+
+/// Ends a [State::batch] when dropped, even if `f` panics.
+struct BatchGuard<'a>(&'a mut State);
+
+impl Drop for BatchGuard<'_> {
+	fn drop(&mut self) {
+		let state = &mut *self.0;
+
+		let batch = state.batch.as_mut().unwrap();
+		batch.depth -= 1;
+
+		if batch.depth == 0 {
+			let batch = state.batch.take().unwrap();
+
+			for (id, changed_fn) in batch.changes {
+				if changed_fn.is_none_or(|changed| changed(state)) {
+					state.mark_readers_dirty(id);
+				}
+			}
+		}
+	}
+}
+
+impl Default for State {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl State {
 	pub fn new() -> Self {
 		let mut arena = arena::Arena::new();
@@ -30,68 +94,215 @@ impl State {
 			window_focused: arena.alloc(true),
 			window_maximized: arena.alloc(false),
 			window_minimized: arena.alloc(false),
+			window_normal_size: arena.alloc((0, 0)),
+			scroll_extents: arena.alloc(HashMap::new()),
 
 			arena,
 			event_state,
 
-			redraw_refs: HashSet::new(),
+			dependencies: RefCell::default(),
+			dirty: HashMap::new(),
+			unmounted: Vec::new(),
+			versions: HashMap::new(),
+			task_state: tasks::TaskState::new(),
+			timer_state: timers::TimerState::new(),
+			effects: reactivity::Effects::default(),
+			history: history::History::new(),
+			persistence: persist::Persistence::default(),
+			contexts: RefCell::default(),
+			remote: std::sync::mpsc::channel(),
+			batch: None,
 			request_redraw: false,
 		}
 	}
 
+	/// Drop all allocated values, invalidating their [arena::Ref]s.
+	/// 
+	/// The window refs are allocated again and keep their values.
 	pub fn clear(&mut self) {
+		let window_size = self.get(self.window_size).copied().unwrap_or_default();
+		let window_pos = self.get(self.window_pos).copied().unwrap_or_default();
+		let window_focused = self.get(self.window_focused).copied().unwrap_or(true);
+		let window_maximized = self.get(self.window_maximized).copied().unwrap_or_default();
+		let window_minimized = self.get(self.window_minimized).copied().unwrap_or_default();
+		let window_normal_size = self.get(self.window_normal_size).copied().unwrap_or_default();
+
 		self.arena.clear();
+		self.versions.clear();
+
+		self.window_size = self.alloc(window_size);
+		self.window_pos = self.alloc(window_pos);
+		self.window_focused = self.alloc(window_focused);
+		self.window_maximized = self.alloc(window_maximized);
+		self.window_minimized = self.alloc(window_minimized);
+		self.window_normal_size = self.alloc(window_normal_size);
+		// the scrolled offsets are gone as well
+		self.scroll_extents = self.alloc(HashMap::new());
 	}
 
-	pub fn alloc<T>(&mut self, val: T) -> arena::Ref<T> where T: Copy + PartialEq {
+	/// Allocate a value within the [State].
+	/// 
+	/// Values do not need to be [Copy], they are dropped together with the [State].
+	pub fn alloc<T: 'static>(&mut self, val: T) -> arena::Ref<T> {
 		self.arena.alloc(val)
 	}
 
-	pub fn get<T>(&self, r: arena::Ref<T>) -> Option<&T> where T: Copy + PartialEq {
+	/// Remove the value behind `r` from the [State] and return it.
+	/// 
+	/// Its memory gets reused, `r` becomes invalid.
+	/// Containers that read the value are marked as dirty.
+	pub fn free<T>(&mut self, r: arena::Ref<T>) -> Option<T> {
+		let val = self.arena.free(r)?;
+
+		self.mark_readers_dirty(r.id());
+		self.versions.remove(&r.id());
+
+		Some(val)
+	}
+
+	/// Get the value behind `r`, recording it as a dependency of the container that is currently being tracked.
+	pub fn get<T>(&self, r: arena::Ref<T>) -> Option<&T> {
+		self.record_read(r.id());
 		self.arena.get(r)
 	}
 
-	pub fn set<T>(&mut self, r: arena::Ref<T>, val: T) -> Option<()> where T: Copy + PartialEq {
+	/// Replace the value behind `r`, notifying its dependents if it is not equal to the old value.
+	pub fn set<T>(&mut self, r: arena::Ref<T>, val: T) -> Option<()> where T: PartialEq + 'static {
 		let p = self.arena.get_mut(r)?;
-		let old = *p;
-		*p = val;
 
-		if old != val {
+		if *p != val {
 			// value has changed, emit event
-			self.emit_ref_changed(r);
+			let old = std::mem::replace(p, val);
+			self.value_changed(r, old);
 		}
 
 		Some(())
 	}
 
+	/// Mutate the value behind `r`, notifying its dependents if it is not equal to the old value.
+	/// 
+	/// For values that are expensive to clone or cannot be compared, use [State::modify].
 	pub fn mutate_ref<T, F>(&mut self, r: arena::Ref<T>, op: F) -> Option<()> where
-		T: Copy + PartialEq,
+		T: Clone + PartialEq + 'static,
 		F: FnOnce(&mut T),
 	{
-		let old = *self.get(r)?;
+		let old = self.arena.get(r)?.clone();
 		// TODO: since the first call to get worked, the other will work as well
 		// we could probably use get_unchecked on the Ref
-		op(self.arena.get_mut(r)?);
-		let new = *self.arena.get(r)?;
+		let new = self.arena.get_mut(r)?;
+		op(new);
 
-		if old != new {
+		if old != *new {
 			// value has changed, emit event
-			self.emit_ref_changed(r);
+			self.value_changed(r, old);
 		}
 
 		Some(())
 	}
 
+	/// Mutate the value behind `r` and always notify its dependents.
+	pub fn modify<T, F>(&mut self, r: arena::Ref<T>, op: F) -> Option<()> where
+		F: FnOnce(&mut T),
+	{
+		op(self.arena.get_mut(r)?);
+		self.emit_ref_changed(r);
+
+		Some(())
+	}
+
+	/// Get the number of times the value behind `r` has changed.
+	pub fn version<T>(&self, r: arena::Ref<T>) -> u64 {
+		self.record_read(r.id());
+		self.versions.get(&r.id()).copied().unwrap_or(0)
+	}
+
+	/// Get the version of a value by its id, or [None] if it is not alive anymore.
+	pub(crate) fn version_of(&self, id: arena::RefId) -> Option<u64> {
+		self.arena.contains_id(id).then(|| self.versions.get(&id).copied().unwrap_or(0))
+	}
+
+	/// Mark all containers that read `r` as dirty, see [State::track].
+	/// 
+	/// Within [State::batch], this is deferred until the batch ends.
 	pub fn emit_ref_changed<T>(&mut self, r: arena::Ref<T>) {
-		if self.redraw_refs.contains(&(r.arena_id(), r.as_ptr().as_ptr() as usize)) {
-			self.request_redraw = true;
+		*self.versions.entry(r.id()).or_insert(0) += 1;
+
+		match &mut self.batch {
+			Some(batch) => {
+				let id = r.id();
+
+				// the value cannot be compared anymore
+				match batch.changes.iter_mut().find(|(changed, _)| *changed == id) {
+					Some((_, changed_fn)) => *changed_fn = None,
+					None => batch.changes.push((id, None)),
+				}
+			},
+			None => self.mark_readers_dirty(r.id()),
 		}
 	}
 
-	pub fn redraw_on_change<T>(&mut self, r: arena::Ref<T>) {
-		self.redraw_refs.insert((r.arena_id(), r.as_ptr().as_ptr() as usize));
+	/// Notify the dependents of `r`, which had the value `old` before.
+	fn value_changed<T: PartialEq + 'static>(&mut self, r: arena::Ref<T>, old: T) {
+		*self.versions.entry(r.id()).or_insert(0) += 1;
+
+		// shared by the history and the batch
+		let old = Rc::new(RefCell::new(old));
+		self.history.record(r, old.clone());
+
+		let batch = match &mut self.batch {
+			Some(b) => b,
+			None => {
+				self.mark_readers_dirty(r.id());
+				return;
+			}
+		};
+
+		let id = r.id();
+
+		// only the value from before the batch matters
+		if batch.changes.iter().all(|(changed, _)| *changed != id) {
+			let changed_fn: ChangedFn = Box::new(move |state: &State| state.arena.get(r).is_none_or(|v| *v != *old.borrow()));
+			batch.changes.push((id, Some(changed_fn)));
+		}
+	}
+
+	/// Run `f`, deferring all change notifications until it returns.
+	/// 
+	/// Changes to the same value are coalesced, its dependents are only notified
+	/// if the value differs from the value it had before the batch.
+	/// Batches can be nested, the outermost batch notifies the dependents.
+	/// 
+	/// Example:
+	/// ```rust
+	/// use unison::*;
+	/// 
+	/// let mut state = State::new();
+	/// let count = state.alloc(0);
+	/// 
+	/// let runs = state.alloc(0);
+	/// state.effect(count, move |_, state| { state.mutate_ref(runs, |r| *r += 1); });
+	/// 
+	/// state.batch(|state| {
+	///     state.set(count, 1);
+	///     state.set(count, 2);
+	/// });
+	/// state.run_effects().unwrap();
+	/// 
+	/// // once initially, once for the batch
+	/// assert_eq!(state.get(runs), Some(&2));
+	/// ```
+	pub fn batch<R, F: FnOnce(&mut State) -> R>(&mut self, f: F) -> R {
+		self.batch.get_or_insert_with(Batch::default).depth += 1;
+
+		let guard = BatchGuard(self);
+		f(guard.0)
 	}
 
+	/// Does nothing. Containers are redrawn when a ref they read during `layout` changes,
+	/// without registering it first.
+	#[deprecated(note = "refs read during layout are tracked automatically")]
+	pub fn redraw_on_change<T>(&mut self, _r: Ref<T>) {}
+
 	pub fn get_event_type<T: 'static>(&mut self, name: &'static str) -> EventType<T> {
 		self.event_state.get_event_type(name)
 	}
@@ -99,4 +310,111 @@ impl State {
 	pub fn emit<T: 'static>(&mut self, ty: EventType<T>, val: T) {
 		self.event_state.emit(ty, val)
 	}
+
+	/// See [EventState::subscribe].
+	/// 
+	/// Subscriptions made while a component is built or handles an event belong to its container,
+	/// they are removed when it is rebuilt or removed from its tree.
+	pub fn subscribe<T, F>(&mut self, ty: EventType<T>, handler: F) -> Subscription where
+		T: 'static,
+		F: FnMut(&T, &mut State) + 'static,
+	{
+		let owner = self.current_owner();
+		self.event_state.subscribe_owned(ty, owner, handler)
+	}
+
+	pub fn unsubscribe(&mut self, sub: Subscription) {
+		self.event_state.unsubscribe(sub)
+	}
+
+	/// Forget everything about a container that has been removed from its tree.
+	pub(crate) fn unmount(&mut self, id: ContainerId) {
+		self.dependencies.borrow_mut().remove(id);
+		self.dirty.remove(&id);
+		self.cancel_owned_tasks(id);
+		self.cancel_owned_timers(id);
+		self.remove_owned_effects(id);
+		self.event_state.remove_owned_subscriptions(id);
+		self.remove_provided(id);
+		self.unmounted.push(id);
+	}
+
+	/// Take the containers that have been removed since the last call.
+	pub(crate) fn take_unmounted(&mut self) -> Vec<ContainerId> {
+		std::mem::take(&mut self.unmounted)
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_batch() {
+		let mut state = State::new();
+
+		let a = state.alloc(1);
+		let b = state.alloc(String::from("b"));
+		let c = state.alloc(vec![1]);
+
+		let (reads_a, reads_b, reads_c) = (ContainerId::new(), ContainerId::new(), ContainerId::new());
+
+		state.track(reads_a, Phase::Layout, || state.get(a));
+		state.track(reads_b, Phase::Layout, || state.get(b));
+		state.track(reads_c, Phase::Draw, || state.get(c));
+
+		state.batch(|state| {
+			state.set(a, 2);
+			state.mutate_ref(b, |b| b.push('!'));
+			state.modify(c, |c| c.clear());
+
+			// nothing is notified within the batch
+			assert!(state.dirty.is_empty());
+
+			// nested batches are flushed by the outermost one
+			state.batch(|state| {
+				state.set(a, 1);
+				state.set(a, 3);
+			});
+			assert!(state.dirty.is_empty());
+
+			// back to the value from before the batch
+			state.set(a, 1);
+
+			// reads see the new values
+			assert_eq!(state.get(b).unwrap(), "b!");
+		});
+
+		assert_eq!(state.is_dirty(reads_a), None);
+		assert_eq!(state.is_dirty(reads_b), Some(Dirty::Layout));
+		assert_eq!(state.is_dirty(reads_c), Some(Dirty::Redraw));
+
+		// versions are bumped for every change
+		assert_eq!(state.version(a), 4);
+	}
+
+	#[test]
+	fn test_batch_panic() {
+		let mut state = State::new();
+
+		let a = state.alloc(1);
+		let reads_a = ContainerId::new();
+		state.track(reads_a, Phase::Layout, || state.get(a));
+
+		let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+			state.batch(|state| {
+				state.set(a, 2);
+				panic!("oops");
+			})
+		}));
+		assert!(result.is_err());
+
+		// the batch has ended and notified the change
+		assert_eq!(state.is_dirty(reads_a), Some(Dirty::Layout));
+		state.take_dirty();
+
+		state.set(a, 3);
+		assert_eq!(state.is_dirty(reads_a), Some(Dirty::Layout));
+	}
 }
@@ -0,0 +1,332 @@
+//! Running futures on the event loop.
+//!
+//! Futures spawned using [State::spawn] are polled on the UI thread whenever they are woken up.
+//! Once a future completes, its output is passed to a closure together with the [State],
+//! so that it can be written into [arena::Ref]s.
+//!
+//! Futures spawned while a component is built or handles an event belong to its container,
+//! they are cancelled when the container is removed from its tree.
+
+use crate::*;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::task::{ Context, Poll, Wake, Waker };
+
+
+/// Identifies a task spawned using [State::spawn].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TaskId(usize);
+
+
+type TaskFuture = Pin<Box<dyn Future<Output = Box<dyn FnOnce(&mut State)>>>>;
+
+struct Task {
+	future: TaskFuture,
+	owner: Option<ContainerId>,
+}
+
+/// Wakes a single task by queueing its id.
+struct TaskWaker {
+	id: TaskId,
+	ready: mpsc::Sender<TaskId>,
+}
+
+impl Wake for TaskWaker {
+	fn wake(self: std::sync::Arc<Self>) {
+		self.wake_by_ref()
+	}
+
+	fn wake_by_ref(self: &std::sync::Arc<Self>) {
+		if self.ready.send(self.id).is_ok() {
+			runtime::wake_event_loop();
+		}
+	}
+}
+
+
+pub(crate) struct TaskState {
+	tasks: HashMap<TaskId, Task>,
+	next_id: usize,
+	/// The containers that are currently building or handling an event, innermost last.
+	owners: Vec<ContainerId>,
+	ready: (mpsc::Sender<TaskId>, mpsc::Receiver<TaskId>),
+}
+
+impl TaskState {
+	pub fn new() -> Self {
+		Self {
+			tasks: HashMap::new(),
+			next_id: 0,
+			owners: Vec::new(),
+			ready: mpsc::channel(),
+		}
+	}
+}
+
+
+impl State {
+	/// Spawn a future, which is polled on the event loop.
+	///
+	/// When it completes, `on_done` is called with its output.
+	///
+	/// Example:
+	/// ```rust
+	/// use unison::*;
+	///
+	/// let mut state = State::new();
+	/// let text = state.alloc(String::new());
+	///
+	/// state.spawn(async { String::from("loaded") }, move |val, state| { state.set(text, val); });
+	/// state.poll_tasks();
+	///
+	/// assert_eq!(state.get(text).unwrap(), "loaded");
+	/// ```
+	pub fn spawn<F, C>(&mut self, future: F, on_done: C) -> TaskId where
+		F: Future + 'static,
+		C: FnOnce(F::Output, &mut State) + 'static,
+	{
+		let tasks = &mut self.task_state;
+
+		let id = TaskId(tasks.next_id);
+		tasks.next_id += 1;
+
+		let future: TaskFuture = Box::pin(async move {
+			let val = future.await;
+			Box::new(move |state: &mut State| on_done(val, state)) as Box<dyn FnOnce(&mut State)>
+		});
+
+		let owner = tasks.owners.last().copied();
+		tasks.tasks.insert(id, Task { future, owner });
+
+		// poll it for the first time
+		let _ = tasks.ready.0.send(id);
+		runtime::wake_event_loop();
+
+		id
+	}
+
+	/// Cancel a task, dropping its future.
+	///
+	/// Returns `false` if the task has already completed or been cancelled.
+	pub fn cancel(&mut self, id: TaskId) -> bool {
+		self.task_state.tasks.remove(&id).is_some()
+	}
+
+	/// Check whether a task is still running.
+	pub fn is_running(&self, id: TaskId) -> bool {
+		self.task_state.tasks.contains_key(&id)
+	}
+
+	/// Poll all tasks that have been woken up.
+	///
+	/// Tasks woken up while polling are polled by the next call, so that a task that keeps waking itself
+	/// does not block the event loop.
+	/// Returns `true` if any task has been polled.
+	pub fn poll_tasks(&mut self) -> bool {
+		let mut polled = false;
+
+		let ready: Vec<_> = self.task_state.ready.1.try_iter().collect();
+
+		for id in ready {
+			// the task might have completed or been cancelled in the meantime
+			let mut task = match self.task_state.tasks.remove(&id) {
+				Some(t) => t,
+				None => continue,
+			};
+
+			polled = true;
+
+			let waker = Waker::from(std::sync::Arc::new(TaskWaker { id, ready: self.task_state.ready.0.clone() }));
+
+			match task.future.as_mut().poll(&mut Context::from_waker(&waker)) {
+				Poll::Ready(on_done) => {
+					// tasks spawned by on_done belong to the same container
+					match task.owner {
+						Some(owner) => self.with_owner(owner, on_done),
+						None => on_done(self),
+					}
+				},
+				Poll::Pending => {
+					self.task_state.tasks.insert(id, task);
+				},
+			}
+		}
+
+		polled
+	}
+
+	/// Run `f`, assigning all tasks spawned within it to the container `id`.
+	pub(crate) fn with_owner<R, F: FnOnce(&mut State) -> R>(&mut self, id: ContainerId, f: F) -> R {
+		self.task_state.owners.push(id);
+		let r = f(self);
+		self.task_state.owners.pop();
+		r
+	}
+
+	/// The container that is currently building or handling an event.
+	pub(crate) fn current_owner(&self) -> Option<ContainerId> {
+		self.task_state.owners.last().copied()
+	}
+
+	/// Cancel all tasks that belong to the container `id`.
+	pub(crate) fn cancel_owned_tasks(&mut self, id: ContainerId) {
+		self.task_state.tasks.retain(|_, t| t.owner != Some(id));
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::cell::RefCell;
+	use std::rc::Rc;
+
+	/// A future that completes once `value` has been set.
+	#[derive(Clone, Default)]
+	struct Slot {
+		value: Rc<RefCell<Option<u32>>>,
+		waker: Rc<RefCell<Option<Waker>>>,
+	}
+
+	impl Slot {
+		fn complete(&self, val: u32) {
+			*self.value.borrow_mut() = Some(val);
+
+			if let Some(w) = self.waker.borrow_mut().take() {
+				w.wake();
+			}
+		}
+	}
+
+	impl Future for Slot {
+		type Output = u32;
+
+		fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+			match self.value.borrow_mut().take() {
+				Some(v) => Poll::Ready(v),
+				None => {
+					*self.waker.borrow_mut() = Some(cx.waker().clone());
+					Poll::Pending
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn test_tasks() {
+		let mut state = State::new();
+		let result = state.alloc(0);
+
+		let slot = Slot::default();
+		let task = state.spawn(slot.clone(), move |val, state| { state.set(result, val); });
+
+		assert!(state.poll_tasks());
+		assert!(state.is_running(task));
+
+		// not woken up, nothing to poll
+		assert!(!state.poll_tasks());
+
+		slot.complete(42);
+		assert!(state.poll_tasks());
+
+		assert!(!state.is_running(task));
+		assert_eq!(state.get(result), Some(&42));
+
+		let slot = Slot::default();
+		let task = state.spawn(slot.clone(), move |val, state| { state.set(result, val); });
+
+		assert!(state.cancel(task));
+		slot.complete(1);
+		state.poll_tasks();
+
+		assert_eq!(state.get(result), Some(&42));
+	}
+
+	/// Wakes itself and yields every time it is polled.
+	struct Yield(Rc<RefCell<u32>>);
+
+	impl Future for Yield {
+		type Output = ();
+
+		fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+			*self.0.borrow_mut() += 1;
+			cx.waker().wake_by_ref();
+			Poll::Pending
+		}
+	}
+
+	#[test]
+	fn test_yield() {
+		let mut state = State::new();
+
+		let polls = Rc::new(RefCell::new(0));
+		let task = state.spawn(Yield(polls.clone()), |_, _| {});
+
+		// polled once per call instead of spinning
+		assert!(state.poll_tasks());
+		assert_eq!(*polls.borrow(), 1);
+
+		assert!(state.poll_tasks());
+		assert_eq!(*polls.borrow(), 2);
+
+		state.cancel(task);
+		assert!(!state.poll_tasks());
+	}
+
+	struct Loader {
+		slot: Slot,
+		result: arena::Ref<u32>,
+	}
+
+	impl Component for Loader {
+		type Child = ();
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			let result = self.result;
+			state.spawn(self.slot.clone(), move |val, state| { state.set(result, val); });
+		}
+	}
+
+	struct Root {
+		show: arena::Ref<bool>,
+		slot: Slot,
+		result: arena::Ref<u32>,
+	}
+
+	impl Component for Root {
+		type Child = Option<Loader>;
+
+		fn build(&self, state: &mut State) -> Self::Child {
+			state.get(self.show).unwrap().then(|| Loader { slot: self.slot.clone(), result: self.result })
+		}
+	}
+
+	#[test]
+	fn test_cancel_on_unmount() {
+		let mut state = State::new();
+		let (show, result) = (state.alloc(true), state.alloc(0));
+
+		let slot = Slot::default();
+		let mut tree = container::ComponentTree::new(Root { show, slot: slot.clone(), result }, &mut state);
+
+		state.poll_tasks();
+
+		// shared by the test, both components and the task
+		assert_eq!(Rc::strong_count(&slot.value), 4);
+
+		state.set(show, false);
+		let dirty = state.take_dirty();
+		tree.update(&mut state, &dirty);
+
+		// the loader has been removed, its task has been dropped
+		assert_eq!(Rc::strong_count(&slot.value), 2);
+
+		slot.complete(3);
+		state.poll_tasks();
+		assert_eq!(state.get(result), Some(&0));
+	}
+}
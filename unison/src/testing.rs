@@ -0,0 +1,281 @@
+//! Golden-image snapshot testing for components, enabled by the `testing` feature.
+//!
+//! Components are rendered offscreen using the [unison_backend_soft::SoftBackend] and compared
+//! against PNGs stored on disk.
+//!
+//! Text is drawn using [TEST_FONT] instead of the system's fonts, so that the images do not depend on the machine.
+//!
+//! When the `UNISON_UPDATE_SNAPSHOTS` environment variable is set, the rendered images are written
+//! as the new references instead. Otherwise, a missing reference is an error.
+//!
+//! Example:
+//! ```rust,no_run
+//! use unison::*;
+//! use unison::testing::Snapshots;
+//!
+//! let mut snapshots = Snapshots::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots"))
+//!     .with_tolerance(2);
+//!
+//! snapshots.assert("label", Label { text: "Hello".to_owned() }, (200, 40));
+//! ```
+
+use crate::*;
+
+use std::path::{ Path, PathBuf };
+
+
+/// DejaVu Sans Mono, the only font known to [Snapshots], see `tests/fonts/LICENSE`.
+pub const TEST_FONT: &[u8] = include_bytes!("../tests/fonts/DejaVuSansMono.ttf");
+
+/// Environment variable that makes [Snapshots::check] write its snapshots instead of comparing them.
+pub const UPDATE_SNAPSHOTS_VAR: &str = "UNISON_UPDATE_SNAPSHOTS";
+
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+	#[error("failed to read or write snapshot: {0}")]
+	Image(#[from] image::ImageError),
+	#[error("snapshot {} does not exist, set {UPDATE_SNAPSHOTS_VAR} to create it", path.display())]
+	Missing {
+		path: PathBuf,
+	},
+	#[error("snapshot has size {expected:?}, but rendered image has size {actual:?}")]
+	SizeMismatch {
+		expected: (u32, u32),
+		actual: (u32, u32),
+	},
+	#[error("{pixels} pixels differ from the snapshot, see {}", diff.display())]
+	Mismatch {
+		pixels: usize,
+		diff: PathBuf,
+	},
+}
+
+
+/// Renders components offscreen and compares them against stored snapshots.
+pub struct Snapshots {
+	dir: PathBuf,
+	tolerance: u8,
+	bcknd: unison_backend_soft::SoftBackend,
+	font_state: FontState,
+}
+
+impl Snapshots {
+	/// Create a new [Snapshots] that stores its images within `dir`.
+	pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+		Self {
+			dir: dir.into(),
+			tolerance: 0,
+			bcknd: unison_backend_soft::SoftBackend::new(),
+			font_state: FontState::with_fonts([TEST_FONT.to_vec()]),
+		}
+	}
+
+	/// Set the maximum difference per color channel for two pixels to still be considered equal.
+	pub fn with_tolerance(mut self, tolerance: u8) -> Self {
+		self.tolerance = tolerance;
+		self
+	}
+
+	/// Render a component with the given window size.
+	pub fn render<T: Component>(&mut self, root: T, window_size: (u32, u32)) -> image::RgbaImage {
+		Page::new(root)
+			.render_offscreen(&mut self.bcknd, window_size, &mut self.font_state)
+			.to_image()
+	}
+
+	/// Render a component and compare it against the snapshot called `name`.
+	///
+	/// On mismatch, the rendered image (`<name>.actual.png`) and a diff image (`<name>.diff.png`)
+	/// are written next to the snapshot.
+	pub fn check<T: Component>(&mut self, name: &str, root: T, window_size: (u32, u32)) -> Result<(), SnapshotError> {
+		let actual = self.render(root, window_size);
+		let path = self.path(name, "png");
+
+		if std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() {
+			std::fs::create_dir_all(&self.dir).map_err(image::ImageError::IoError)?;
+			actual.save(&path)?;
+			return Ok(());
+		}
+
+		if !path.exists() {
+			return Err(SnapshotError::Missing { path });
+		}
+
+		let expected = image::open(&path)?.into_rgba8();
+
+		if expected.dimensions() != actual.dimensions() {
+			return Err(SnapshotError::SizeMismatch {
+				expected: expected.dimensions(),
+				actual: actual.dimensions(),
+			});
+		}
+
+		if let Some((pixels, diff_img)) = compare(&expected, &actual, self.tolerance) {
+			let diff = self.path(name, "diff.png");
+
+			actual.save(self.path(name, "actual.png"))?;
+			diff_img.save(&diff)?;
+
+			return Err(SnapshotError::Mismatch { pixels, diff });
+		}
+
+		Ok(())
+	}
+
+	/// Like [Snapshots::check], but panics on mismatch.
+	pub fn assert<T: Component>(&mut self, name: &str, root: T, window_size: (u32, u32)) {
+		if let Err(e) = self.check(name, root, window_size) {
+			panic!("snapshot `{}` does not match: {}", name, e);
+		}
+	}
+
+	fn path(&self, name: &str, ext: &str) -> PathBuf {
+		Path::join(&self.dir, format!("{}.{}", name, ext))
+	}
+}
+
+
+/// Compare two images of the same size.
+///
+/// Returns [None] if no channel of any pixel differs by more than `tolerance`.
+/// Otherwise, returns the number of differing pixels and a diff image,
+/// which highlights them in red on top of a faded version of `expected`.
+pub fn compare(expected: &image::RgbaImage, actual: &image::RgbaImage, tolerance: u8) -> Option<(usize, image::RgbaImage)> {
+	let mut diff = image::RgbaImage::new(expected.width(), expected.height());
+	let mut pixels = 0;
+
+	for ((e, a), d) in expected.pixels().zip(actual.pixels()).zip(diff.pixels_mut()) {
+		let differs = e.0.iter().zip(a.0.iter()).any(|(e, a)| e.abs_diff(*a) > tolerance);
+
+		*d = if differs {
+			pixels += 1;
+			image::Rgba([255, 0, 0, 255])
+		} else {
+			let luma = (e.0[0] as u32 + e.0[1] as u32 + e.0[2] as u32) / 3;
+			let faded = (luma / 4 + 64) as u8;
+			image::Rgba([faded, faded, faded, 255])
+		};
+	}
+
+	if pixels == 0 {
+		None
+	} else {
+		Some((pixels, diff))
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	struct Fill(Color, u32);
+
+	impl Component for Fill {
+		type Child = ();
+
+		fn build(&self, _: &mut State) -> Self::Child {}
+
+		fn draw<'a, B: Backend>(&self, _: &State, view: &mut B::View<'a>, _: &mut FontState) {
+			view.fill(self.0.into());
+		}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let mut layout = Layout::new();
+			layout.set_flex(self.1);
+			layout
+		}
+	}
+
+	struct Stack(Orientation);
+
+	impl Component for Stack {
+		type Child = (Fill, Fill, Fill);
+
+		fn build(&self, _: &mut State) -> Self::Child {
+			(
+				Fill(Color(1.0, 0.0, 0.0, 1.0), 1),
+				Fill(Color(0.0, 1.0, 0.0, 1.0), 2),
+				Fill(Color(0.0, 0.0, 1.0, 1.0), 1),
+			)
+		}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let mut layout = Layout::new();
+			layout.set_stack_orientation(self.0);
+			layout.set_stack_spacing(4);
+			layout.set_padding(Bounds::new(2, 2, 2, 2));
+			layout
+		}
+	}
+
+	/// Shows a label on a white background, as labels are drawn in black.
+	struct Paper(&'static str);
+
+	impl Component for Paper {
+		type Child = Label;
+
+		fn build(&self, _: &mut State) -> Self::Child {
+			Label { text: String::from(self.0) }
+		}
+
+		fn draw<'a, B: Backend>(&self, _: &State, view: &mut B::View<'a>, _: &mut FontState) {
+			view.fill(Color(1.0, 1.0, 1.0, 1.0).into());
+		}
+
+		fn layout(&self, _: &mut State) -> Layout {
+			let mut layout = Layout::new();
+			layout.set_padding(Bounds::new(2, 4, 2, 4));
+			layout
+		}
+	}
+
+	fn snapshots() -> Snapshots {
+		Snapshots::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots"))
+	}
+
+	#[test]
+	fn test_flex_distribution() {
+		let mut snapshots = snapshots();
+
+		snapshots.assert("flex_horizontal", Stack(Orientation::Horizontal), (64, 16));
+		snapshots.assert("flex_vertical", Stack(Orientation::Vertical), (16, 64));
+	}
+
+	#[test]
+	fn test_label() {
+		let mut snapshots = snapshots();
+
+		snapshots.assert("label", Paper("Hello, unison!"), (160, 20));
+	}
+
+	#[test]
+	fn test_missing() {
+		if std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() {
+			return;
+		}
+
+		let mut snapshots = snapshots();
+		let err = snapshots.check("does_not_exist", Stack(Orientation::Horizontal), (8, 8)).unwrap_err();
+
+		assert!(matches!(err, SnapshotError::Missing { .. }));
+		assert!(!snapshots.path("does_not_exist", "png").exists());
+	}
+
+	#[test]
+	fn test_compare() {
+		let expected = image::RgbaImage::from_pixel(4, 4, image::Rgba([10, 10, 10, 255]));
+		let mut actual = expected.clone();
+
+		assert!(compare(&expected, &actual, 0).is_none());
+
+		actual.put_pixel(1, 2, image::Rgba([12, 10, 10, 255]));
+		assert!(compare(&expected, &actual, 2).is_none());
+
+		let (pixels, diff) = compare(&expected, &actual, 1).unwrap();
+		assert_eq!(pixels, 1);
+		assert_eq!(diff.get_pixel(1, 2).0, [255, 0, 0, 255]);
+		assert_ne!(diff.get_pixel(0, 0).0, [255, 0, 0, 255]);
+	}
+}
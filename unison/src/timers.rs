@@ -0,0 +1,316 @@
+//! Timers and frame callbacks.
+//!
+//! Timers fire once or repeatedly after a [Duration], frame callbacks are called before the next frame is drawn.
+//! The event loop waits until the next timer is due and keeps polling while frame callbacks are pending.
+//!
+//! Like tasks, timers and frame callbacks scheduled by a component are cancelled when its container is removed.
+
+use crate::*;
+
+use std::cell::RefCell;
+use std::collections::{ HashMap, HashSet };
+use std::time::{ Duration, Instant };
+
+
+/// Identifies a timer scheduled using [State::after] or [State::every].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerId(usize);
+
+
+type TimerCallback = Box<dyn FnMut(&mut State)>;
+
+struct Timer {
+	deadline: Instant,
+	/// Set for repeating timers.
+	interval: Option<Duration>,
+	/// Taken out while the timer is running.
+	callback: Option<TimerCallback>,
+	owner: Option<ContainerId>,
+}
+
+type FrameCallback = Box<dyn FnOnce(Instant, &mut State)>;
+
+
+pub(crate) struct TimerState {
+	timers: HashMap<TimerId, Timer>,
+	next_id: usize,
+	frame_callbacks: Vec<(Option<ContainerId>, FrameCallback)>,
+	/// Containers that have to be tracked again in the next frame, because an animation is running.
+	animating: RefCell<HashSet<(ContainerId, Phase)>>,
+	frame_time: Instant,
+}
+
+impl TimerState {
+	pub fn new() -> Self {
+		Self {
+			timers: HashMap::new(),
+			next_id: 0,
+			frame_callbacks: Vec::new(),
+			animating: RefCell::default(),
+			frame_time: Instant::now(),
+		}
+	}
+}
+
+
+impl State {
+	/// Call `f` once after `delay`.
+	pub fn after<F: FnOnce(&mut State) + 'static>(&mut self, delay: Duration, f: F) -> TimerId {
+		let mut f = Some(f);
+
+		self.schedule(delay, None, Box::new(move |state| {
+			if let Some(f) = f.take() {
+				f(state)
+			}
+		}))
+	}
+
+	/// Call `f` every `interval`, until the timer is cancelled.
+	pub fn every<F: FnMut(&mut State) + 'static>(&mut self, interval: Duration, f: F) -> TimerId {
+		self.schedule(interval, Some(interval), Box::new(f))
+	}
+
+	fn schedule(&mut self, delay: Duration, interval: Option<Duration>, callback: TimerCallback) -> TimerId {
+		let owner = self.current_owner();
+		let timers = &mut self.timer_state;
+
+		let id = TimerId(timers.next_id);
+		timers.next_id += 1;
+
+		timers.timers.insert(id, Timer {
+			deadline: Instant::now() + delay,
+			interval,
+			callback: Some(callback),
+			owner,
+		});
+
+		id
+	}
+
+	/// Cancel a timer.
+	///
+	/// Returns `false` if the timer has already fired or been cancelled.
+	pub fn cancel_timer(&mut self, id: TimerId) -> bool {
+		self.timer_state.timers.remove(&id).is_some()
+	}
+
+	/// Call `f` with the frame timestamp before the next frame is drawn.
+	///
+	/// To animate continuously, request another frame from within `f`.
+	pub fn request_frame<F: FnOnce(Instant, &mut State) + 'static>(&mut self, f: F) {
+		let owner = self.current_owner();
+		self.timer_state.frame_callbacks.push((owner, Box::new(f)));
+		self.request_redraw = true;
+	}
+
+	/// Check whether any frame callbacks or animations are waiting for the next frame.
+	pub fn has_frame_callbacks(&self) -> bool {
+		!self.timer_state.frame_callbacks.is_empty() || !self.timer_state.animating.borrow().is_empty()
+	}
+
+	/// Get the timestamp of the current frame, see [State::run_frame_callbacks].
+	pub fn frame_time(&self) -> Instant {
+		self.timer_state.frame_time
+	}
+
+	/// Track the current phase of the container that is currently being tracked again in the next frame.
+	pub(crate) fn request_animation_frame(&self) {
+		if let Some(current) = self.dependencies.borrow().current() {
+			self.timer_state.animating.borrow_mut().insert(current);
+		}
+	}
+
+	/// Get the time at which the next timer is due.
+	pub fn next_deadline(&self) -> Option<Instant> {
+		self.timer_state.timers.values().map(|t| t.deadline).min()
+	}
+
+	/// Fire all timers that are due at `now`, in the order of their deadlines.
+	///
+	/// Returns `true` if any timer has fired.
+	pub fn run_timers(&mut self, now: Instant) -> bool {
+		let mut due: Vec<(Instant, TimerId)> = self.timer_state.timers.iter()
+			.filter(|(_, t)| t.deadline <= now)
+			.map(|(id, t)| (t.deadline, *id))
+			.collect();
+
+		due.sort_by_key(|(deadline, id)| (*deadline, id.0));
+
+		for (_, id) in &due {
+			// an earlier callback might have cancelled this timer
+			let timer = match self.timer_state.timers.get_mut(id) {
+				Some(t) => t,
+				None => continue,
+			};
+
+			// the callback is missing while the timer is already firing, when a callback runs the timers itself
+			let (mut callback, owner) = match timer.callback.take() {
+				Some(callback) => (callback, timer.owner),
+				None => continue,
+			};
+
+			match timer.interval {
+				Some(interval) => {
+					timer.deadline += interval;
+
+					// skip the intervals we have missed
+					if timer.deadline <= now {
+						timer.deadline = now + interval;
+					}
+				},
+				None => {
+					self.timer_state.timers.remove(id);
+				}
+			}
+
+			match owner {
+				Some(owner) => self.with_owner(owner, |state| callback(state)),
+				None => callback(self),
+			}
+
+			// put the callback back, unless the timer cancelled itself
+			if let Some(timer) = self.timer_state.timers.get_mut(id) {
+				timer.callback = Some(callback);
+			}
+		}
+
+		!due.is_empty()
+	}
+
+	/// Start a new frame at `frame_time`.
+	///
+	/// Calls all frame callbacks that have been requested before this call
+	/// and marks containers with running animations as dirty.
+	pub fn run_frame_callbacks(&mut self, frame_time: Instant) {
+		self.timer_state.frame_time = frame_time;
+
+		for (id, phase) in self.timer_state.animating.take() {
+			let dirty = self.dirty.entry(id).or_insert(Dirty::Redraw);
+			*dirty = (*dirty).max(phase.into());
+		}
+
+		let callbacks = std::mem::take(&mut self.timer_state.frame_callbacks);
+
+		for (owner, f) in callbacks {
+			match owner {
+				Some(owner) => self.with_owner(owner, |state| f(frame_time, state)),
+				None => f(frame_time, self),
+			}
+		}
+	}
+
+	/// Cancel all timers and frame callbacks that belong to the container `id`.
+	pub(crate) fn cancel_owned_timers(&mut self, id: ContainerId) {
+		self.timer_state.timers.retain(|_, t| t.owner != Some(id));
+		self.timer_state.frame_callbacks.retain(|(owner, _)| *owner != Some(id));
+		self.timer_state.animating.borrow_mut().retain(|(owner, _)| *owner != id);
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_timers() {
+		let mut state = State::new();
+		let start = Instant::now();
+
+		let once = state.alloc(0);
+		let repeated = state.alloc(0);
+
+		state.after(Duration::from_millis(10), move |state| { state.mutate_ref(once, |v| *v += 1); });
+		let timer = state.every(Duration::from_millis(5), move |state| { state.mutate_ref(repeated, |v| *v += 1); });
+
+		assert!(state.next_deadline().unwrap() >= start + Duration::from_millis(5));
+
+		assert!(!state.run_timers(start));
+
+		state.run_timers(start + Duration::from_millis(7));
+		assert_eq!(state.get(once), Some(&0));
+		assert_eq!(state.get(repeated), Some(&1));
+
+		// missed intervals only fire once
+		state.run_timers(start + Duration::from_millis(30));
+		assert_eq!(state.get(once), Some(&1));
+		assert_eq!(state.get(repeated), Some(&2));
+
+		assert!(state.cancel_timer(timer));
+		assert!(state.next_deadline().is_none());
+		assert!(!state.run_timers(start + Duration::from_secs(1)));
+	}
+
+	#[test]
+	fn test_cancel_from_callback() {
+		let mut state = State::new();
+		let start = Instant::now();
+
+		let count = state.alloc(0);
+		let timer = state.alloc(None);
+
+		let id = state.every(Duration::from_millis(1), move |state| {
+			state.mutate_ref(count, |v| *v += 1);
+
+			if let Some(id) = state.get(timer).copied().flatten() {
+				state.cancel_timer(id);
+			}
+		});
+		state.set(timer, Some(id));
+
+		state.run_timers(start + Duration::from_millis(5));
+
+		assert_eq!(state.get(count), Some(&1));
+		assert!(state.next_deadline().is_none());
+	}
+
+	#[test]
+	fn test_run_timers_from_callback() {
+		let mut state = State::new();
+		let start = Instant::now();
+
+		let count = state.alloc(0);
+
+		// the nested call finds the timer due again, but skips it while it is firing
+		state.every(Duration::from_millis(1), move |state| {
+			state.mutate_ref(count, |v| *v += 1);
+			state.run_timers(start + Duration::from_millis(100));
+		});
+
+		state.run_timers(start + Duration::from_millis(5));
+
+		assert_eq!(state.get(count), Some(&1));
+		assert!(state.next_deadline().is_some());
+	}
+
+	#[test]
+	fn test_frame_callbacks() {
+		let mut state = State::new();
+		let start = Instant::now();
+
+		let frames = state.alloc(Vec::new());
+
+		fn animate(frames: arena::Ref<Vec<Instant>>, state: &mut State) {
+			state.request_frame(move |time, state| {
+				state.modify(frames, |f| f.push(time));
+
+				if state.get(frames).unwrap().len() < 2 {
+					animate(frames, state);
+				}
+			});
+		}
+
+		animate(frames, &mut state);
+		assert!(state.has_frame_callbacks());
+		assert!(state.request_redraw);
+
+		// frames requested during a frame run in the next one
+		state.run_frame_callbacks(start);
+		assert_eq!(state.get(frames).unwrap().len(), 1);
+		assert!(state.has_frame_callbacks());
+
+		state.run_frame_callbacks(start + Duration::from_millis(16));
+		assert_eq!(state.get(frames).unwrap(), &[start, start + Duration::from_millis(16)]);
+		assert!(!state.has_frame_callbacks());
+	}
+}
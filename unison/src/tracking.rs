@@ -0,0 +1,260 @@
+//! Tracking of which containers read which [arena::Ref]s.
+//!
+//! While a component is built, laid out or drawn, every [arena::Ref] read through the [State]
+//! (via [LazyValue::eval] or [State::get]) is recorded as a dependency of that container.
+//! When the value behind the [arena::Ref] changes, the depending containers are marked [Dirty].
+
+use crate::*;
+use crate::arena::RefId;
+
+use std::collections::{ HashMap, HashSet };
+
+
+/// Identifies a container within all [container::ComponentTree]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContainerId(usize);
+
+impl ContainerId {
+	pub(crate) fn new() -> Self {
+		static CONTAINER_IDX: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+		Self(CONTAINER_IDX.fetch_add(1, std::sync::atomic::Ordering::Relaxed))
+	}
+}
+
+
+/// The part of a container that read a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+	Build,
+	Layout,
+	Draw,
+}
+
+/// What has to be done to a container after a value it depends on has changed.
+///
+/// Each variant implies the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Dirty {
+	Redraw,
+	Layout,
+	Rebuild,
+}
+
+impl From<Phase> for Dirty {
+	fn from(value: Phase) -> Self {
+		match value {
+			Phase::Build => Self::Rebuild,
+			Phase::Layout => Self::Layout,
+			Phase::Draw => Self::Redraw,
+		}
+	}
+}
+
+
+#[derive(Default)]
+pub(crate) struct Dependencies {
+	/// The containers that are currently being tracked, innermost last.
+	stack: Vec<(ContainerId, Phase)>,
+	/// Which containers read a value.
+	readers: HashMap<RefId, HashSet<(ContainerId, Phase)>>,
+	/// Which values a container read, used to forget the old dependencies when tracking again.
+	reads: HashMap<(ContainerId, Phase), HashSet<RefId>>,
+	/// Values read within [State::capture_reads], innermost last.
+	captures: Vec<HashSet<RefId>>,
+}
+
+impl Dependencies {
+	/// Start tracking `phase` of the container `id`, forgetting the dependencies it had before.
+	pub fn push(&mut self, id: ContainerId, phase: Phase) {
+		self.forget(id, phase);
+		self.stack.push((id, phase));
+	}
+
+	/// Continue tracking `phase` of the container `id`, keeping the dependencies it had before.
+	pub fn resume(&mut self, id: ContainerId, phase: Phase) {
+		self.stack.push((id, phase));
+	}
+
+	pub fn pop(&mut self) {
+		self.stack.pop();
+	}
+
+	/// Forget all dependencies of a container.
+	pub fn remove(&mut self, id: ContainerId) {
+		self.forget(id, Phase::Build);
+		self.forget(id, Phase::Layout);
+		self.forget(id, Phase::Draw);
+	}
+
+	/// Record a read of `r` by the container that is currently being tracked.
+	pub fn record(&mut self, r: RefId) {
+		if let Some(capture) = self.captures.last_mut() {
+			capture.insert(r);
+		}
+
+		let reader = match self.stack.last() {
+			Some(r) => *r,
+			None => return,
+		};
+
+		if self.reads.entry(reader).or_default().insert(r) {
+			self.readers.entry(r).or_default().insert(reader);
+		}
+	}
+
+	/// Get the container that is currently being tracked.
+	pub fn current(&self) -> Option<(ContainerId, Phase)> {
+		self.stack.last().copied()
+	}
+
+	/// Get all containers that read `r`.
+	pub fn readers(&self, r: RefId) -> impl Iterator<Item = (ContainerId, Phase)> + '_ {
+		self.readers.get(&r).into_iter().flatten().copied()
+	}
+
+	fn forget(&mut self, id: ContainerId, phase: Phase) {
+		let reads = match self.reads.remove(&(id, phase)) {
+			Some(r) => r,
+			None => return,
+		};
+
+		for r in reads {
+			if let Some(readers) = self.readers.get_mut(&r) {
+				readers.remove(&(id, phase));
+
+				if readers.is_empty() {
+					self.readers.remove(&r);
+				}
+			}
+		}
+	}
+}
+
+
+impl State {
+	/// Run `f`, recording all values read through this [State] as dependencies of `phase` of the container `id`.
+	///
+	/// Dependencies recorded by an earlier call for the same container and phase are replaced.
+	pub fn track<R, F: FnOnce() -> R>(&self, id: ContainerId, phase: Phase, f: F) -> R {
+		self.dependencies.borrow_mut().push(id, phase);
+		let r = f();
+		self.dependencies.borrow_mut().pop();
+		r
+	}
+
+	/// Like [State::track], but passes the [State] to `f` mutably.
+	pub fn track_mut<R, F: FnOnce(&mut State) -> R>(&mut self, id: ContainerId, phase: Phase, f: F) -> R {
+		self.dependencies.borrow_mut().push(id, phase);
+		let r = f(self);
+		self.dependencies.borrow_mut().pop();
+		r
+	}
+
+	/// Like [State::track], but keeps the dependencies recorded by the last call to [State::track].
+	pub(crate) fn track_resumed<R, F: FnOnce() -> R>(&self, id: ContainerId, phase: Phase, f: F) -> R {
+		self.dependencies.borrow_mut().resume(id, phase);
+		let r = f();
+		self.dependencies.borrow_mut().pop();
+		r
+	}
+
+	pub(crate) fn record_read(&self, r: RefId) {
+		self.dependencies.borrow_mut().record(r);
+	}
+
+	/// Check whether the container `id` has to be rebuilt, laid out or redrawn.
+	pub fn is_dirty(&self, id: ContainerId) -> Option<Dirty> {
+		self.dirty.get(&id).copied()
+	}
+
+	/// Take all dirty containers, marking them as clean.
+	pub fn take_dirty(&mut self) -> HashMap<ContainerId, Dirty> {
+		std::mem::take(&mut self.dirty)
+	}
+
+	/// Run `f`, returning all values read through this [State] in addition to recording them as usual.
+	pub(crate) fn capture_reads<R, F: FnOnce() -> R>(&self, f: F) -> (R, HashSet<RefId>) {
+		self.dependencies.borrow_mut().captures.push(HashSet::new());
+		let r = f();
+
+		let mut deps = self.dependencies.borrow_mut();
+		let reads = deps.captures.pop().unwrap();
+
+		// the reads are part of any outer capture as well
+		if let Some(outer) = deps.captures.last_mut() {
+			outer.extend(reads.iter().copied());
+		}
+
+		(r, reads)
+	}
+
+	/// Mark all containers that depend on `r` as dirty.
+	pub(crate) fn mark_readers_dirty(&mut self, r: RefId) {
+		let deps = self.dependencies.borrow();
+
+		for (id, phase) in deps.readers(r) {
+			if self.effects.contains(id) {
+				self.effects.mark_pending(id);
+				continue;
+			}
+
+			let dirty = self.dirty.entry(id).or_insert(Dirty::Redraw);
+			*dirty = (*dirty).max(phase.into());
+
+			self.request_redraw = true;
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_tracking() {
+		let mut state = State::new();
+
+		let a = state.alloc(1u32);
+		let b = state.alloc(2u32);
+		let c = state.alloc(3u32);
+
+		let (outer, inner) = (ContainerId::new(), ContainerId::new());
+
+		let binding = bind!(a => a * 2);
+
+		state.track(outer, Phase::Layout, || {
+			binding.eval(&state);
+
+			state.track(inner, Phase::Draw, || state.get(b));
+		});
+
+		// reads outside of any tracked container are not recorded
+		state.get(c);
+
+		state.set(c, 4);
+		assert!(state.take_dirty().is_empty());
+		assert!(!state.request_redraw);
+
+		state.set(b, 5);
+		assert_eq!(state.is_dirty(inner), Some(Dirty::Redraw));
+		assert_eq!(state.is_dirty(outer), None);
+
+		state.set(a, 2);
+		assert_eq!(state.is_dirty(outer), Some(Dirty::Layout));
+		assert!(state.request_redraw);
+
+		assert_eq!(state.take_dirty().len(), 2);
+		assert_eq!(state.is_dirty(outer), None);
+
+		// tracking again replaces the old dependencies
+		state.track(outer, Phase::Layout, || state.get(c));
+
+		state.set(a, 3);
+		assert_eq!(state.is_dirty(outer), None);
+
+		state.set(c, 5);
+		assert_eq!(state.is_dirty(outer), Some(Dirty::Layout));
+	}
+}